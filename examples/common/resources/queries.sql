@@ -8,12 +8,21 @@ CREATE TABLE users
   created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
 );
 
+-- :name execute_create_indexes :script
+-- :doc Creates indexes of users table, one statement after another.
+CREATE INDEX users_name_idx ON users(name);
+-- users are often listed by creation time; no need to index emails, they're unique already
+CREATE INDEX users_created_at_idx ON users(created_at);
+
 -- :name execute_drop_table
 DROP TABLE users;
 
 -- :name execute_insert_user
 INSERT INTO users(user_id, email, name, picture) VALUES($1, $2, $3, $4);
 
+-- :name execute_insert_users :batch
+INSERT INTO users(user_id, email, name, picture) VALUES($1, $2, $3, $4);
+
 -- :name untyped_get_user_by_id             :1
 SELECT * FROM users WHERE user_id = $1 LIMIT 1;
 -- :name untyped_get_user_by_name           :?
@@ -31,6 +40,14 @@ SELECT * FROM users WHERE name LIKE $1 LIMIT 1;
 SELECT * FROM users;
 -- :name typed_get_stream_users             :typed :^
 SELECT * FROM users;
+-- :name typed_count_users                  :typed :scalar
+SELECT count(*) FROM users;
+-- :name typed_user_exists                  :typed :exists
+SELECT 1 FROM users WHERE user_id = $1;
+-- :name typed_touch_user                   :typed :n
+UPDATE users SET name = name WHERE user_id = $1;
+-- :name touch_users                        :affects 0..=1
+UPDATE users SET name = name WHERE user_id >= $1;
 
 -- :name mapped_get_user_by_id               :mapped :1
 SELECT * FROM users WHERE user_id = $1 LIMIT 1;
@@ -41,8 +58,154 @@ SELECT * FROM users;
 -- :name mapped_get_stream_users             :mapped :^
 SELECT * FROM users;
 
+-- :name try_mapped_get_user_by_id           :try_mapped :1
+SELECT * FROM users WHERE user_id = $1 LIMIT 1;
+-- :name try_mapped_get_user_by_name         :try_mapped :?
+SELECT * FROM users WHERE name LIKE $1 LIMIT 1;
+-- :name try_mapped_get_multiple_users       :try_mapped :*
+SELECT * FROM users;
+-- :name try_mapped_get_stream_users         :try_mapped :^
+SELECT * FROM users;
+-- :name async_mapped_get_multiple_users     :async_mapped :*
+SELECT * FROM users;
+-- :name async_mapped_get_stream_users       :async_mapped :^
+SELECT * FROM users;
+
+-- :name typed_get_chunked_users             :typed :^3
+SELECT * FROM users;
+-- :name untyped_get_chunked_users           :chunks 2
+SELECT * FROM users;
+-- :name try_mapped_get_chunked_users        :try_mapped :^3
+SELECT * FROM users;
+
+-- :name typed_get_paged_users               :typed :paged
+SELECT * FROM users WHERE user_id >= $1
+ORDER BY user_id; -- oldest first
+-- :name untyped_get_paged_users             :paged
+-- :order_key name, user_id
+SELECT * FROM users -- all of them
+-- :name typed_get_paged_users_desc         :typed :paged
+-- :order_key name DESC, user_id
+SELECT * FROM users
+-- :name typed_get_recent_users             :typed :paged
+-- :order_key created_at DESC, user_id
+SELECT * FROM users
+-- :name execute_set_created_at
+UPDATE users SET created_at = $1 WHERE user_id = $2;
+
+-- :name typed_get_sorted_users              :typed :*
+SELECT * FROM users
+--~order_by sort { user_id, name, email } default user_id
+;
+
+-- :name typed_users_by_id                  :typed :map user_id
+SELECT * FROM users;
+-- :name untyped_users_by_initial           :group initial
+SELECT substr(name, 1, 1) AS initial, users.* FROM users ORDER BY user_id;
+-- :name typed_users_with_namesakes         :typed :nest user_id namesake_
+-- :doc Users along with other users whose names start with the same letter.
+SELECT u.user_id, u.name, n.user_id AS namesake_id, n.name AS namesake_name
+FROM users u
+LEFT JOIN users n ON substr(n.name, 1, 1) = substr(u.name, 1, 1) AND n.user_id <> u.user_id
+ORDER BY u.user_id, n.user_id;
+
 --:name conditional_query                    :typed :*
 SELECT * FROM users
 --~{ limited
 LIMIT 1;
 --~}
+
+--:name loop_query                           :typed :*
+SELECT * FROM users
+WHERE
+--~for name in names
+name LIKE :name
+--~join OR
+--~end
+ORDER BY user_id;
+
+--:name where_query                          :typed :*
+SELECT * FROM users
+--~where
+--~{ first_ones
+AND user_id < 3
+--~}
+--~{ last_ones
+AND user_id > 1
+--~}
+--~end
+ORDER BY user_id;
+
+--:name set_query                            :typed :n
+UPDATE users
+--~set
+--~{ same_name
+name = name,
+--~}
+--~{ same_picture
+picture = picture
+--~}
+--~end
+WHERE user_id = $1;
+
+--:name optional_query                       :typed :*
+-- :param name Option<String>
+SELECT * FROM users
+--~where
+--~{ ?name
+name = :name
+--~}
+--~{ ?user_id
+user_id = :user_id OR user_id = :user_id + 1
+--~}
+--~end
+ORDER BY user_id;
+
+--:name cfg_query                            :typed :*
+-- :cfg unix
+SELECT * FROM users
+--~cfg(debug_assertions)
+WHERE user_id = 1
+--~end
+
+-- :snippet user_columns
+user_id, email, name, picture, created_at
+
+--:name snippet_query                        :typed :*
+SELECT
+--~include user_columns
+FROM {{table}}
+ORDER BY user_id;
+
+--:name identifiers_query                    :typed :*
+SELECT * FROM {{:table}} -- of tenant, rather than {{:schema}}
+--~{ limited
+LIMIT 1
+--~}
+
+--:name value_list_query                     :typed :*
+SELECT * FROM users
+WHERE user_id IN (:v*:ids)
+ORDER BY user_id;
+
+--:name excluded_value_list_query            :typed :*
+SELECT * FROM users
+WHERE user_id NOT IN (:v*:ids)
+ORDER BY user_id;
+
+--:name sorted_query                         :typed :*
+-- :ident sort_column in (user_id, name)
+SELECT * FROM users
+ORDER BY :i:sort_column DESC;
+
+--:name grouped_query                        :typed :*
+SELECT * FROM users
+WHERE :i:column IS NOT NULL
+ORDER BY user_id;
+
+--:name insert_users_query
+-- :param rows Vec<(i32, String, String, String)>
+INSERT INTO users(user_id, email, name, picture) VALUES :t*:rows;
+
+--:name delete_users_query :raw
+DELETE FROM users WHERE user_id IN (:v*:ids);
@@ -1,11 +1,12 @@
 use common::{expected_users, sample_data, User};
 use futures::TryStreamExt;
 use hugsqlx::{params, HugSqlx};
-use sqlx::{mysql::MySqlRow, Row, MySqlPool};
+use sqlx::{mysql::MySqlRow, MySqlPool, Row};
 use std::env;
 
 #[derive(HugSqlx)]
 #[queries = "../common/resources/queries.sql"]
+#[hugsqlx(vars(table = "users"))]
 struct Users {}
 
 #[tokio::main]
@@ -13,15 +14,19 @@ async fn main() -> anyhow::Result<()> {
     let pool = MySqlPool::connect(&env::var("DATABASE_URL")?).await?;
 
     Users::execute_create_table(&pool, params!()).await?;
+    let statements = Users::execute_create_indexes(&pool, true).await?;
+    assert_eq!(statements.len(), 2);
     println!("Users table created. Feeding with sample data...");
 
-    for (uid, email, name, pic) in sample_data() {
-        Users::execute_insert_user(&pool, params!(uid, email, name, pic)).await?;
-    }
+    let params = sample_data().map(|(uid, email, name, pic)| params!(uid, email, name, pic));
+    Users::execute_insert_users(&pool, params).await?;
     let expected_users = expected_users();
 
     let users = Users::typed_get_multiple_users::<_, User>(&pool, params!()).await?;
-    println!("{} users inserted. Continuing with some tests:", users.len());
+    println!(
+        "{} users inserted. Continuing with some tests:",
+        users.len()
+    );
 
     print!("  * Typed results...      ");
     typed_example(&pool, &expected_users).await?;
@@ -0,0 +1,9 @@
+-- :name bulk_insert_users :bulk
+-- :doc Inserts all the users at once, binding an array per column.
+-- :param users &[User]
+-- :fields users (user_id, email, name, picture)
+INSERT INTO users(user_id, email, name, picture)
+SELECT * FROM UNNEST($1::int[], $2::text[], $3::text[], $4::text[]);
+
+-- :name delete_users
+DELETE FROM users WHERE user_id >= $1;
@@ -0,0 +1,10 @@
+-- :name cursor_users :typed :cursor 3
+-- :doc Streams users through a server-side cursor, 3 rows fetched at once.
+SELECT * FROM users WHERE user_id >= $1 ORDER BY user_id;
+
+-- :name cursor_user_rows :cursor 2
+SELECT * FROM users ORDER BY user_id
+--~{ limited
+LIMIT 1
+--~}
+;
@@ -1,27 +1,52 @@
 use common::{expected_users, sample_data, User};
 use futures::TryStreamExt;
 use hugsqlx::{params, HugSqlx};
-use sqlx::{postgres::PgRow, Row, PgPool};
+use sqlx::{postgres::PgRow, PgPool, Row};
 use std::env;
 
 #[derive(HugSqlx)]
 #[queries = "../common/resources/queries.sql"]
+#[hugsqlx(vars(table = "users"))]
 struct Users {}
 
+// Each set of queries gets its own HugSql trait, so it needs its own module
+mod bulk {
+    use common::User;
+    use hugsqlx::HugSqlx;
+
+    #[derive(HugSqlx)]
+    #[queries = "resources/bulk.sql"]
+    pub struct BulkUsers {}
+}
+use bulk::{BulkUsers, HugSql as _};
+
+mod cursors {
+    use hugsqlx::HugSqlx;
+
+    #[derive(HugSqlx)]
+    #[queries = "resources/cursors.sql"]
+    pub struct CursorUsers {}
+}
+use cursors::{CursorUsers, HugSql as _};
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let pool = PgPool::connect(&env::var("DATABASE_URL")?).await?;
 
     Users::execute_create_table(&pool, params!()).await?;
+    let statements = Users::execute_create_indexes(&pool, true).await?;
+    assert_eq!(statements.len(), 2);
     println!("Users table created. Feeding with sample data...");
 
-    for (uid, email, name, pic) in sample_data() {
-        Users::execute_insert_user(&pool, params!(uid, email, name, pic)).await?;
-    }
+    let params = sample_data().map(|(uid, email, name, pic)| params!(uid, email, name, pic));
+    Users::execute_insert_users(&pool, params).await?;
     let expected_users = expected_users();
 
     let users = Users::typed_get_multiple_users::<_, User>(&pool, params!()).await?;
-    println!("{} users inserted. Continuing with some tests:", users.len());
+    println!(
+        "{} users inserted. Continuing with some tests:",
+        users.len()
+    );
 
     print!("  * Typed results...      ");
     typed_example(&pool, &expected_users).await?;
@@ -32,6 +57,12 @@ async fn main() -> anyhow::Result<()> {
     print!("  * Mapped results...     ");
     mapped_example(&pool, &expected_users).await?;
 
+    print!("  * Bulk insert...        ");
+    bulk_example(&pool).await?;
+
+    print!("  * Cursor results...     ");
+    cursor_example(&pool, &expected_users).await?;
+
     Users::execute_drop_table(&pool, params!()).await?;
     println!("Dropped users table.");
 
@@ -127,6 +158,78 @@ async fn typed_example(pool: &PgPool, expected: &[User]) -> anyhow::Result<()> {
     while let Some(got) = rows.try_next().await? {
         assert_eq!(&got, it.next().unwrap());
     }
+
+    let got = Users::value_list_query::<_, User>(pool, &[] as &[i32], params!()).await?;
+    assert!(got.is_empty());
+
+    let got = Users::excluded_value_list_query::<_, User>(pool, &[] as &[i32], params!()).await?;
+    assert_eq!(got, expected);
+    println!("[OK]");
+    Ok(())
+}
+
+async fn bulk_example(pool: &PgPool) -> anyhow::Result<()> {
+    let users: Vec<_> = (100..110)
+        .map(|user_id| User {
+            user_id,
+            email: format!("user{user_id}@example.com"),
+            name: format!("User {user_id}"),
+            picture: format!("user{user_id}.png"),
+        })
+        .collect();
+    let got = BulkUsers::bulk_insert_users(pool, &users, params!()).await?;
+    assert_eq!(got.rows_affected, users.len() as u64);
+    assert_eq!(got.last_insert_id, None);
+
+    let got = BulkUsers::delete_users(pool, params!(100)).await?;
+    assert_eq!(got.rows_affected, users.len() as u64);
+
+    // Postgres reports no id of inserted rows
+    let got =
+        Users::execute_insert_user(pool, params!(5, "eve@example.com", "Eve", "eve.png")).await?;
+    assert_eq!((got.rows_affected, got.last_insert_id), (1, None));
+    BulkUsers::delete_users(pool, params!(5)).await?;
+
+    println!("[OK]");
+    Ok(())
+}
+
+async fn cursor_example(pool: &PgPool, expected: &[User]) -> anyhow::Result<()> {
+    let users = CursorUsers::cursor_users::<_, User>(pool, params!(1))
+        .await
+        .try_collect::<Vec<_>>()
+        .await?;
+    assert_eq!(users, expected);
+
+    // Stream dropped halfway rolls back transaction, closing the cursor
+    let mut rows = CursorUsers::cursor_user_rows(pool, |_| false, params!()).await;
+    let row = rows.try_next().await?.unwrap();
+    assert_eq!(row.try_get::<i32, _>("user_id")?, 1);
+    drop(rows);
+
+    let rows = CursorUsers::cursor_user_rows(pool, |_| true, params!())
+        .await
+        .try_collect::<Vec<_>>()
+        .await?;
+    assert_eq!(rows.len(), 1);
+
+    // The same query streamed again within one transaction gets a cursor of its own
+    let mut tx = pool.begin().await?;
+    let mut rows = CursorUsers::cursor_user_rows(&mut *tx, |_| false, params!()).await;
+    rows.try_next().await?.unwrap();
+    drop(rows);
+    let users = CursorUsers::cursor_users::<_, User>(&mut *tx, params!(1))
+        .await
+        .try_collect::<Vec<_>>()
+        .await?;
+    assert_eq!(users, expected);
+    let rows = CursorUsers::cursor_user_rows(&mut *tx, |_| false, params!())
+        .await
+        .try_collect::<Vec<_>>()
+        .await?;
+    assert_eq!(rows.len(), expected.len());
+    tx.commit().await?;
+
     println!("[OK]");
     Ok(())
 }
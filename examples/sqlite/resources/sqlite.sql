@@ -0,0 +1,8 @@
+-- :name execute_replace_user
+REPLACE INTO users(user_id, email, name, picture) VALUES($1, $2, $3, $4);
+
+-- :name insert_pictured_users_query
+-- :doc Inserts users sharing a picture, bound once per chunk of rows.
+-- :param rows Vec<(i32, String, String)>
+INSERT INTO users(user_id, email, name, picture)
+SELECT column1, column2, column3, ? FROM (VALUES :t*:rows);
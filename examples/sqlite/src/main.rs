@@ -1,13 +1,27 @@
+use chrono::{NaiveDate, NaiveDateTime};
 use common::{expected_users, sample_data, User};
 use futures::TryStreamExt;
-use hugsqlx::{params, HugSqlx};
-use sqlx::{sqlite::SqliteRow, Row, SqlitePool};
+use hugsqlx::{
+    params, BatchError, ExecOutcome, HugSqlx, Identifiers, Nest, NestChild, PageCursor,
+    PageRequest, RowsAffectedError, SortDirection,
+};
+use sqlx::{sqlite::SqliteRow, Execute, Row, SqlitePool};
 use std::env;
 
 #[derive(HugSqlx)]
 #[queries = "../common/resources/queries.sql"]
+#[hugsqlx(vars(table = "users"))]
 struct Users {}
 
+mod sqlite {
+    use hugsqlx::HugSqlx;
+
+    #[derive(HugSqlx)]
+    #[queries = "resources/sqlite.sql"]
+    pub struct SqliteUsers {}
+}
+use sqlite::{HugSql as _, SqliteUsers};
+
 fn user_mapper(row: SqliteRow) -> User {
     User {
         user_id: row.get("user_id"),
@@ -17,20 +31,75 @@ fn user_mapper(row: SqliteRow) -> User {
     }
 }
 
+fn try_user_mapper(row: SqliteRow) -> Result<User, sqlx::Error> {
+    Ok(User {
+        user_id: row.try_get("user_id")?,
+        email: row.try_get("email")?,
+        name: row.try_get("name")?,
+        picture: row.try_get("picture")?,
+    })
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Namesake {
+    user_id: i32,
+    name: String,
+}
+
+impl NestChild<SqliteRow> for Namesake {
+    fn from_prefixed_row(
+        row: &SqliteRow,
+        prefix: &str,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(Namesake {
+            user_id: row.try_get(format!("{prefix}id").as_str())?,
+            name: row.try_get(format!("{prefix}name").as_str())?,
+        })
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct UserNamesakes {
+    user_id: i32,
+    name: String,
+    #[sqlx(skip)]
+    namesakes: Vec<Namesake>,
+}
+
+impl Nest for UserNamesakes {
+    type Child = Namesake;
+
+    fn children(&mut self) -> &mut Vec<Namesake> {
+        &mut self.namesakes
+    }
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let pool = SqlitePool::connect(&env::var("DATABASE_URL")?).await?;
 
     Users::execute_create_table(&pool, params!()).await?;
+    let statements = Users::execute_create_indexes(&pool, true).await?;
+    assert_eq!(statements.len(), 2);
     println!("Users table created. Feeding with sample data...");
 
-    for (uid, email, name, pic) in sample_data() {
-        Users::execute_insert_user(&pool, params!(uid, email, name, pic)).await?;
-    }
+    let params = sample_data().map(|(uid, email, name, pic)| params!(uid, email, name, pic));
+    Users::execute_insert_users(&pool, params).await?;
+
+    // Duplicated user rolls back the whole batch
+    let params = [
+        params!(5, "eve@example.com", "Eve", "eve.png"),
+        params!(1, "alice@example.com", "Alice", "alice.png"),
+    ];
+    let got = Users::execute_insert_users(&pool, params).await;
+    assert!(matches!(got, Err(BatchError { index: Some(1), .. })));
     let expected_users = expected_users();
 
     let users = Users::typed_get_multiple_users::<_, User>(&pool, params!()).await?;
-    println!("{} users inserted. Continuing with some tests:", users.len());
+    println!(
+        "{} users inserted. Continuing with some tests:",
+        users.len()
+    );
 
     print!("  * Typed results...      ");
     typed_example(&pool, &expected_users).await?;
@@ -41,9 +110,33 @@ async fn main() -> anyhow::Result<()> {
     print!("  * Mapped results...     ");
     mapped_example(&pool, &expected_users).await?;
 
+    print!("  * Try mapped results... ");
+    try_mapped_example(&pool, &expected_users).await?;
+
+    print!("  * Async mapped...       ");
+    async_mapped_example(&pool, &expected_users).await?;
+
+    print!("  * Grouped results...    ");
+    grouped_example(&pool, &expected_users).await?;
+
+    print!("  * Chunked results...    ");
+    chunked_example(&pool, &expected_users).await?;
+
+    print!("  * Paged results...      ");
+    paged_example(&pool, &expected_users).await?;
+
+    print!("  * Sorted results...     ");
+    sorted_example(&pool, &expected_users).await?;
+
+    print!("  * Query builders...     ");
+    query_builder_example(&pool, &expected_users).await?;
+
     print!("  * Conditional blocks... ");
     conditional_example(&pool, &expected_users).await?;
 
+    print!("  * Loop blocks...        ");
+    loop_example(&pool, &expected_users).await?;
+
     Users::execute_drop_table(&pool, params!()).await?;
     println!("Dropped users table.");
 
@@ -52,12 +145,15 @@ async fn main() -> anyhow::Result<()> {
 
 async fn untyped_example(pool: &SqlitePool, expected: &[User]) -> anyhow::Result<()> {
     let row = Users::untyped_get_user_by_id(pool, params!(1)).await?;
-    assert_eq!( User {
-        user_id: row.try_get("user_id")?,
-        email: row.try_get("email")?,
-        name: row.try_get("name")?,
-        picture: row.try_get("picture")?,
-    }, expected[0]);
+    assert_eq!(
+        User {
+            user_id: row.try_get("user_id")?,
+            email: row.try_get("email")?,
+            name: row.try_get("name")?,
+            picture: row.try_get("picture")?,
+        },
+        expected[0]
+    );
 
     let row = Users::untyped_get_user_by_name(pool, params!("no_such_a_name")).await?;
     assert!(row.is_none());
@@ -113,6 +209,265 @@ async fn mapped_example(pool: &SqlitePool, expected: &[User]) -> anyhow::Result<
     Ok(())
 }
 
+async fn try_mapped_example(pool: &SqlitePool, expected: &[User]) -> anyhow::Result<()> {
+    let user = Users::try_mapped_get_user_by_id(pool, params!(1), try_user_mapper).await?;
+    assert_eq!(user, expected[0]);
+
+    let row = Users::try_mapped_get_user_by_name(pool, params!("Name_Not_exist"), try_user_mapper)
+        .await?;
+    assert!(row.is_none());
+
+    let mut it = expected.iter();
+    let rows = Users::try_mapped_get_multiple_users(pool, params!(), try_user_mapper).await?;
+    for user in rows {
+        assert_eq!(&user, it.next().unwrap());
+    }
+
+    let mut it = expected.iter();
+    let mut rows = Users::try_mapped_get_stream_users(pool, params!(), try_user_mapper).await;
+    while let Some(user) = rows.try_next().await? {
+        assert_eq!(&user, it.next().unwrap());
+    }
+
+    // Mapping error is reported rather than panicking
+    let got = Users::try_mapped_get_user_by_id(pool, params!(1), |row| {
+        row.try_get::<i64, _>("no_such_column")
+    })
+    .await;
+    assert!(matches!(got, Err(sqlx::Error::ColumnNotFound(_))));
+    println!("[OK]");
+    Ok(())
+}
+
+async fn async_mapped_example(pool: &SqlitePool, expected: &[User]) -> anyhow::Result<()> {
+    // Mapper resolving related data with another query
+    let mapper = |row: SqliteRow| async move {
+        let user = try_user_mapper(row)?;
+        let exists = Users::typed_user_exists(pool, params!(user.user_id)).await?;
+        Ok((user, exists))
+    };
+
+    let mut it = expected.iter();
+    let rows = Users::async_mapped_get_multiple_users(pool, params!(), mapper).await?;
+    for (user, exists) in rows {
+        assert_eq!(&user, it.next().unwrap());
+        assert!(exists);
+    }
+
+    let mut it = expected.iter();
+    let mut rows = Users::async_mapped_get_stream_users(pool, params!(), mapper).await;
+    while let Some((user, exists)) = rows.try_next().await? {
+        assert_eq!(&user, it.next().unwrap());
+        assert!(exists);
+    }
+
+    // Rows get mapped in order, up to the first mapping error
+    let mapped = std::sync::Mutex::new(Vec::new());
+    let mapper = |row: SqliteRow| {
+        let user = try_user_mapper(row);
+        async {
+            let user = user?;
+            mapped.lock().unwrap().push(user.user_id);
+            match user.user_id {
+                2 => Err(sqlx::Error::RowNotFound),
+                _ => Ok(user),
+            }
+        }
+    };
+    let got = Users::async_mapped_get_multiple_users(pool, params!(), mapper).await;
+    assert!(matches!(got, Err(sqlx::Error::RowNotFound)));
+    assert_eq!(*mapped.lock().unwrap(), [1, 2]);
+
+    let mut rows = Users::async_mapped_get_stream_users(pool, params!(), |row| async move {
+        try_user_mapper(row).and_then(|user| match user.user_id {
+            2 => Err(sqlx::Error::RowNotFound),
+            _ => Ok(user),
+        })
+    })
+    .await;
+    assert_eq!(rows.try_next().await?.as_ref(), Some(&expected[0]));
+    assert!(matches!(
+        rows.try_next().await,
+        Err(sqlx::Error::RowNotFound)
+    ));
+    println!("[OK]");
+    Ok(())
+}
+
+async fn grouped_example(pool: &SqlitePool, expected: &[User]) -> anyhow::Result<()> {
+    let users = Users::typed_users_by_id::<_, i32, User>(pool, params!()).await?;
+    assert_eq!(users.len(), expected.len());
+    for user in expected {
+        assert_eq!(&users[&user.user_id], user);
+    }
+
+    let groups = Users::untyped_users_by_initial::<_, String>(pool, params!()).await?;
+    assert_eq!(groups.len(), 3);
+    let names = groups["R"]
+        .iter()
+        .map(|row| row.try_get("name"))
+        .collect::<Result<Vec<String>, _>>()?;
+    assert_eq!(names, ["Robert", "Richard"]);
+
+    let users = Users::typed_users_with_namesakes::<_, i32, UserNamesakes>(pool, params!()).await?;
+    assert_eq!(users.len(), expected.len());
+    for (user, expected) in users.iter().zip(expected) {
+        assert_eq!(
+            (user.user_id, &user.name),
+            (expected.user_id, &expected.name)
+        );
+    }
+    // Outer joined columns of Alice and Charlie are all NULL, so they get no namesakes
+    let namesake = |user_id: i32, name: &str| Namesake {
+        user_id,
+        name: name.to_string(),
+    };
+    assert!(users[0].namesakes.is_empty());
+    assert_eq!(users[1].namesakes, [namesake(4, "Richard")]);
+    assert!(users[2].namesakes.is_empty());
+    assert_eq!(users[3].namesakes, [namesake(2, "Robert")]);
+    println!("[OK]");
+    Ok(())
+}
+
+async fn chunked_example(pool: &SqlitePool, expected: &[User]) -> anyhow::Result<()> {
+    let chunks = Users::typed_get_chunked_users::<_, User>(pool, params!())
+        .await
+        .try_collect::<Vec<_>>()
+        .await?;
+    assert_eq!(chunks.iter().map(Vec::len).collect::<Vec<_>>(), [3, 1]);
+    assert!(chunks.iter().flatten().eq(expected));
+
+    let mut chunks = Users::untyped_get_chunked_users(pool, params!()).await;
+    while let Some(rows) = chunks.try_next().await? {
+        assert_eq!(rows.len(), 2);
+    }
+
+    let chunks = Users::try_mapped_get_chunked_users(pool, params!(), try_user_mapper)
+        .await
+        .try_collect::<Vec<_>>()
+        .await?;
+    assert!(chunks.iter().flatten().eq(expected));
+
+    // Results preceding an error come in a shorter chunk, followed by the error
+    let mut chunks = Users::try_mapped_get_chunked_users(pool, params!(), |row| {
+        try_user_mapper(row).and_then(|user| match user.user_id {
+            3 => Err(sqlx::Error::RowNotFound),
+            _ => Ok(user),
+        })
+    })
+    .await;
+    assert_eq!(chunks.try_next().await?.as_deref(), Some(&expected[..2]));
+    assert!(matches!(
+        chunks.try_next().await,
+        Err(sqlx::Error::RowNotFound)
+    ));
+    assert!(chunks.try_next().await?.is_none());
+    println!("[OK]");
+    Ok(())
+}
+
+async fn paged_example(pool: &SqlitePool, expected: &[User]) -> anyhow::Result<()> {
+    let page = PageRequest::first(3).with_total();
+    let first = Users::typed_get_paged_users::<_, User>(pool, params!(1), page).await?;
+    assert_eq!(first.items, expected[..3]);
+    assert_eq!(first.total, Some(4));
+    assert!(first.has_more);
+
+    // Cursor survives a round trip through a string
+    let cursor = PageCursor::from(first.next_cursor.unwrap().to_string());
+    let page = PageRequest::after(3, cursor);
+    let second = Users::typed_get_paged_users::<_, User>(pool, params!(1), page).await?;
+    assert_eq!(second.items, expected[3..]);
+    assert_eq!(
+        (second.next_cursor, second.has_more, second.total),
+        (None, false, None)
+    );
+
+    // Pages by keyset, ordered by names
+    let mut names = Vec::new();
+    let mut page = PageRequest::first(2);
+    loop {
+        let got = Users::untyped_get_paged_users::<_, String, i32>(pool, params!(), page).await?;
+        for row in got.items {
+            names.push(row.try_get::<String, _>("name")?);
+        }
+        match got.next_cursor {
+            Some(cursor) => page = PageRequest::after(2, cursor),
+            None => break,
+        }
+    }
+    assert_eq!(names, ["Alice", "Charlie", "Richard", "Robert"]);
+
+    // Mixed directions, names descending
+    let mut users = Vec::new();
+    let mut page = PageRequest::first(3);
+    loop {
+        let got = Users::typed_get_paged_users_desc::<_, User, String, i32>(pool, params!(), page)
+            .await?;
+        users.extend(got.items);
+        match got.next_cursor {
+            Some(cursor) => page = PageRequest::after(3, cursor),
+            None => break,
+        }
+    }
+    let names = users
+        .iter()
+        .map(|user| user.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["Robert", "Richard", "Charlie", "Alice"]);
+
+    // Creation times differing by a fraction of a second survive the trip through cursors
+    let created_at = |millis| {
+        NaiveDate::from_ymd_opt(2024, 1, 1)
+            .unwrap()
+            .and_hms_milli_opt(10, 0, 0, millis)
+            .unwrap()
+    };
+    for (user_id, millis) in [(1, 500), (2, 250), (3, 750), (4, 750)] {
+        Users::execute_set_created_at(pool, params!(created_at(millis), user_id)).await?;
+    }
+    let mut users = Vec::new();
+    let mut page = PageRequest::first(1);
+    loop {
+        let got =
+            Users::typed_get_recent_users::<_, User, NaiveDateTime, i32>(pool, params!(), page)
+                .await?;
+        users.extend(got.items.into_iter().map(|user| user.user_id));
+        match got.next_cursor {
+            Some(cursor) => page = PageRequest::after(1, cursor),
+            None => break,
+        }
+    }
+    assert_eq!(users, [3, 4, 1, 2]);
+
+    let got =
+        Users::typed_get_paged_users::<_, User>(pool, params!(1), PageRequest::first(0)).await;
+    assert!(matches!(got, Err(sqlx::Error::Decode(_))));
+
+    let page = PageRequest::after(2, PageCursor::from("o2".to_string()));
+    let got = Users::untyped_get_paged_users::<_, String, i32>(pool, params!(), page).await;
+    assert!(matches!(got, Err(sqlx::Error::Decode(_))));
+    println!("[OK]");
+    Ok(())
+}
+
+async fn sorted_example(pool: &SqlitePool, expected: &[User]) -> anyhow::Result<()> {
+    let users =
+        Users::typed_get_sorted_users::<_, User>(pool, Default::default(), params!()).await?;
+    assert_eq!(users, expected);
+
+    let sort = (TypedGetSortedUsersSort::Name, SortDirection::Desc);
+    let users = Users::typed_get_sorted_users::<_, User>(pool, sort, params!()).await?;
+    let names = users
+        .iter()
+        .map(|user| user.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["Robert", "Richard", "Charlie", "Alice"]);
+    println!("[OK]");
+    Ok(())
+}
+
 async fn typed_example(pool: &SqlitePool, expected: &[User]) -> anyhow::Result<()> {
     let user = Users::typed_get_user_by_id::<_, User>(pool, params!(1)).await?;
     assert_eq!(user, expected[0]);
@@ -131,15 +486,235 @@ async fn typed_example(pool: &SqlitePool, expected: &[User]) -> anyhow::Result<(
     while let Some(got) = rows.try_next().await? {
         assert_eq!(&got, it.next().unwrap());
     }
+
+    let count = Users::typed_count_users::<_, i64>(pool, params!()).await?;
+    assert_eq!(count, expected.len() as i64);
+
+    assert!(Users::typed_user_exists(pool, params!(1)).await?);
+    assert!(!Users::typed_user_exists(pool, params!(100)).await?);
+
+    let rows_affected = Users::typed_touch_user(pool, params!(1)).await?;
+    assert_eq!(rows_affected, 1);
+
+    let result = Users::touch_users(pool, params!(4)).await?;
+    assert_eq!(result.rows_affected, 1);
+
+    let got = Users::touch_users(pool, params!(1)).await;
+    assert!(matches!(
+        got,
+        Err(RowsAffectedError::Unexpected {
+            rows_affected: 4,
+            ..
+        })
+    ));
+
+    let rows = Users::snippet_query::<_, User>(pool, params!()).await?;
+    assert_eq!(rows, expected);
+
+    let idents = Identifiers::new().with("table", "users");
+    let rows = Users::identifiers_query::<_, User>(pool, &idents, |_| false, params!()).await?;
+    assert_eq!(rows, expected);
+    println!("[OK]");
+    Ok(())
+}
+
+async fn query_builder_example(pool: &SqlitePool, expected: &[User]) -> anyhow::Result<()> {
+    let user = Users::typed_get_user_by_id_query::<User>(params!(1))
+        .persistent(false)
+        .fetch_one(pool)
+        .await?;
+    assert_eq!(user, expected[0]);
+
+    let users = Users::try_mapped_get_multiple_users_query(params!(), try_user_mapper)
+        .fetch_all(pool)
+        .await?;
+    assert_eq!(users, expected);
+
+    let count = Users::typed_count_users_query::<i64>(params!())
+        .fetch_one(pool)
+        .await?;
+    assert_eq!(count, expected.len() as i64);
+
+    let query = Users::conditional_query_query::<User>(|_| true, params!());
+    assert_eq!(query.sql().trim(), "SELECT * FROM users\nLIMIT 1;");
+    let users = query.fetch_all(pool).await?;
+    assert_eq!(users.len(), 1);
+
+    let mut sql = String::new();
+    let users = Users::value_list_query_query::<User>(&mut sql, &[2, 3], params!())?
+        .fetch_all(pool)
+        .await?;
+    assert_eq!(users, &expected[1..3]);
+    assert!(sql.contains("IN (?, ?)"));
     println!("[OK]");
     Ok(())
 }
 
-async fn conditional_example(pool: &SqlitePool, _expected: &[User]) -> anyhow::Result<()> {
-    let got = Users::conditional_query::<_, User>(pool, |cond_id| match cond_id {
-        ConditionalQuery::Limited => true
-    }, params!()).await?;
+async fn conditional_example(pool: &SqlitePool, expected: &[User]) -> anyhow::Result<()> {
+    let got = Users::conditional_query::<_, User>(
+        pool,
+        |cond_id| match cond_id {
+            ConditionalQuery::Limited => true,
+        },
+        params!(),
+    )
+    .await?;
+    assert_eq!(got.len(), 1);
+
+    let got = Users::where_query::<_, User>(
+        pool,
+        |cond_id| match cond_id {
+            WhereQuery::FirstOnes => true,
+            WhereQuery::LastOnes => true,
+        },
+        params!(),
+    )
+    .await?;
+    assert_eq!(got.len(), 1);
+    assert_eq!(got[0], expected[1]);
+
+    let got = Users::where_query::<_, User>(pool, |_| false, params!()).await?;
+    assert_eq!(got.len(), expected.len());
+
+    #[cfg(unix)]
+    {
+        let got = Users::cfg_query::<_, User>(pool, params!()).await?;
+        assert_eq!(
+            got.len(),
+            if cfg!(debug_assertions) {
+                1
+            } else {
+                expected.len()
+            }
+        );
+    }
+
+    let got = Users::where_query::<_, User>(pool, WhereQueryFlags::default(), params!()).await?;
+    assert_eq!(got, expected);
+
+    let flags = WhereQueryFlags::default().last_ones(true);
+    let got = Users::where_query::<_, User>(pool, flags, params!()).await?;
+    assert_eq!(got.len(), 3);
+    assert_eq!(got[0], expected[1]);
+
+    let flags = SetQueryFlags::default().same_picture(true);
+    let got = Users::set_query(pool, flags, params!(2)).await?;
+    assert_eq!(got, 1);
+
+    // Nothing to set would leave an invalid UPDATE
+    let got = Users::set_query(pool, SetQueryFlags::default(), params!(2)).await;
+    assert!(matches!(got, Err(sqlx::Error::Protocol(_))));
+
+    let got =
+        Users::optional_query::<_, User>(pool, Some("Robert".to_string()), Some(1), params!())
+            .await?;
     assert_eq!(got.len(), 1);
+    assert_eq!(got[0], expected[1]);
+
+    let got = Users::optional_query::<_, User>(pool, None, Some(3), params!()).await?;
+    assert_eq!(got.len(), 2);
+    assert_eq!(got[0], expected[2]);
+
+    let got = Users::optional_query::<_, User>(pool, None, None::<i32>, params!()).await?;
+    assert_eq!(got.len(), expected.len());
+
+    println!("[OK]");
+    Ok(())
+}
+
+async fn loop_example(pool: &SqlitePool, expected: &[User]) -> anyhow::Result<()> {
+    let got = Users::loop_query::<_, User>(pool, &["Alice", "Rich%"], params!()).await?;
+    assert_eq!(got.len(), 2);
+    assert_eq!(got[0], expected[0]);
+    assert_eq!(got[1], expected[3]);
+
+    // Nothing to repeat would leave a dangling WHERE
+    let got = Users::loop_query::<_, User>(pool, &[] as &[&str], params!()).await;
+    assert!(matches!(got, Err(sqlx::Error::Protocol(_))));
+
+    let got = Users::value_list_query::<_, User>(pool, &[2, 3], params!()).await?;
+    assert_eq!(got, &expected[1..3]);
+
+    let got = Users::value_list_query::<_, User>(pool, &[] as &[i32], params!()).await?;
+    assert!(got.is_empty());
+
+    let got = Users::excluded_value_list_query::<_, User>(pool, &[2, 3], params!()).await?;
+    assert_eq!(got.len(), 2);
+    assert_eq!(got[0], expected[0]);
+    assert_eq!(got[1], expected[3]);
+
+    let got = Users::excluded_value_list_query::<_, User>(pool, &[] as &[i32], params!()).await?;
+    assert_eq!(got, expected);
+
+    let got =
+        Users::sorted_query::<_, User>(pool, SortedQuerySortColumn::UserId, params!()).await?;
+    assert_eq!(got[0], expected[expected.len() - 1]);
+
+    let got = Users::grouped_query::<_, User>(pool, "picture", params!()).await?;
+    assert_eq!(got, expected);
+
+    let got = Users::grouped_query::<_, User>(pool, "picture; DROP TABLE users", params!()).await;
+    assert!(matches!(got, Err(sqlx::Error::Encode(_))));
+
+    // Too many parameters for a single statement, inserted in chunks
+    let rows: Vec<_> = (100..10100)
+        .map(|uid| {
+            (
+                uid,
+                format!("user{uid}@example.com"),
+                format!("User {uid}"),
+                format!("user{uid}.png"),
+            )
+        })
+        .collect();
+    let got = Users::insert_users_query(pool, &rows, params!()).await?;
+    assert_eq!(got.rows_affected, rows.len() as u64);
+    assert_eq!(got.last_insert_id, Some(10099));
+
+    // Only inserts report id of the last inserted row, which is kept per connection
+    let got = Users::touch_users(pool, params!(10099)).await?;
+    assert_eq!(
+        got,
+        ExecOutcome {
+            rows_affected: 1,
+            last_insert_id: None
+        }
+    );
+
+    let got =
+        Users::execute_insert_user(pool, params!(5, "eve@example.com", "Eve", "eve.png")).await?;
+    assert_eq!(got.last_insert_id, Some(5));
+
+    let got =
+        SqliteUsers::execute_replace_user(pool, params!(5, "eve@example.com", "Eve", "eve.jpg"))
+            .await?;
+    assert_eq!(got.last_insert_id, Some(5));
+
+    let got = Users::delete_users_query(pool, &[5], params!()).await?;
+    assert_eq!(got.rows_affected(), 1);
+
+    let got = Users::insert_users_query(pool, &[], params!()).await?;
+    assert_eq!(got, ExecOutcome::default());
+
+    let ids: Vec<_> = rows.iter().map(|row| row.0).collect();
+    let got = Users::delete_users_query(pool, &ids, params!()).await?;
+    assert_eq!(got.rows_affected(), rows.len() as u64);
+
+    // Chunks leave room for the other parameter, bound again with each chunk
+    let rows: Vec<_> = (100..1100)
+        .map(|uid| (uid, format!("user{uid}@example.com"), format!("User {uid}")))
+        .collect();
+    let got = SqliteUsers::insert_pictured_users_query(pool, &rows, params!("user.png")).await?;
+    assert_eq!(got.rows_affected, rows.len() as u64);
+    let users = Users::typed_get_multiple_users::<_, User>(pool, params!()).await?;
+    assert_eq!(users.len(), expected.len() + rows.len());
+    assert!(users[expected.len()..]
+        .iter()
+        .all(|user| user.picture == "user.png"));
+
+    let ids: Vec<_> = rows.iter().map(|row| row.0).collect();
+    let got = Users::delete_users_query(pool, &ids, params!()).await?;
+    assert_eq!(got.rows_affected(), rows.len() as u64);
 
     println!("[OK]");
     Ok(())
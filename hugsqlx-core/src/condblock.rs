@@ -1,11 +1,81 @@
+use std::collections::BTreeMap;
+
+use crate::runtime::Clause;
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub(crate) enum SqlBlock {
     Literal(String),
     Conditional(String, String),
+    /// Block included when parameter of given name is provided
+    Optional(String, String),
+    Loop {
+        var: String,
+        collection: String,
+        body: String,
+        separator: String,
+    },
+    Where(Vec<SqlBlock>),
+    Set(Vec<SqlBlock>),
+    /// Blocks included when configuration predicate holds at compile time
+    Cfg(String, Vec<SqlBlock>),
+    /// Snippet pasted in place of `--~include` directive
+    Include(String),
+    /// `ORDER BY` clause of a column (and direction) chosen at runtime among allowed ones
+    OrderBy {
+        name: String,
+        columns: Vec<String>,
+        default: String,
+    },
+}
+
+/// Condition deciding whether a block gets included
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Condition<'a> {
+    /// Conditional block identifier
+    Block(&'a str),
+    /// Configuration predicate
+    Cfg(&'a str),
+}
+
+/// Piece of SQL fragment which is either copied verbatim or replaced by a bind placeholder.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Fragment {
+    Sql(String),
+    Bind(String),
+}
+
+/// Piece of SQL fragment which is either copied verbatim or expanded at runtime, depending
+/// on function argument of given name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Expansion {
+    Sql(String),
+    /// Comma-separated placeholders of collection elements
+    ValueList(String),
+    /// Quoted identifier
+    Identifier(String),
+    /// Comma-separated, parenthesized placeholders of tuple fields
+    TupleList(String),
+    /// Quoted identifier of `{{:name}}` slot, passed with `Identifiers`
+    Slot(String),
 }
 
 const BLOCK_OPEN: &[char] = &['-', '-', '~', '{'];
 const BLOCK_CLOSE: &[char] = &['-', '-', '~', '}'];
+const LOOP_OPEN: &[char] = &['-', '-', '~', 'f', 'o', 'r', ' '];
+const LOOP_JOIN: &[char] = &['-', '-', '~', 'j', 'o', 'i', 'n'];
+const LOOP_CLOSE: &[char] = &['-', '-', '~', 'e', 'n', 'd'];
+const WHERE_OPEN: &[char] = &['-', '-', '~', 'w', 'h', 'e', 'r', 'e'];
+const SET_OPEN: &[char] = &['-', '-', '~', 's', 'e', 't'];
+const CFG_OPEN: &[char] = &['-', '-', '~', 'c', 'f', 'g', '('];
+const INCLUDE: &[char] = &['-', '-', '~', 'i', 'n', 'c', 'l', 'u', 'd', 'e', ' '];
+const ORDER_BY: &[char] = &['-', '-', '~', 'o', 'r', 'd', 'e', 'r', '_', 'b', 'y', ' '];
+const REGION_CLOSE: &[char] = LOOP_CLOSE;
+const IDENTIFIER_SLOT: &[char] = &['{', '{', ':'];
+const SLOT_CLOSE: &[char] = &['}', '}'];
+const VALUE_LIST: &[char] = &['v', '*', ':'];
+const IDENTIFIER: &[char] = &['i', ':'];
+const TUPLE_LIST: &[char] = &['t', '*', ':'];
+const DEFAULT_SEPARATOR: &str = ",";
 
 fn is_at_newline_or_start(input: &[char], pos: usize) -> bool {
     pos == 0 || (pos > 0 && input[pos - 1] == '\n')
@@ -33,7 +103,20 @@ fn trim_slice(chars: &[char]) -> &[char] {
     }
 }
 
-fn parse_conditional_block(input: &[char], start: usize) -> Option<(String, String, usize)> {
+pub(crate) fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    matches!(chars.next(), Some(c) if c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+fn skip_line(input: &[char], mut i: usize) -> usize {
+    while i < input.len() && input[i] != '\n' {
+        i += 1;
+    }
+    i
+}
+
+fn parse_conditional_block(input: &[char], start: usize) -> Option<(SqlBlock, usize)> {
     let mut i = start + 4; // Skip "--~{"
 
     // Extract condition identifier
@@ -55,11 +138,68 @@ fn parse_conditional_block(input: &[char], start: usize) -> Option<(String, Stri
             let content = trim_slice(&input[content_start..i]);
 
             // Move past the closing tag and its newline
-            i += 4; // "--~}"
-            while i < input.len() && input[i] != '\n' {
-                i += 1;
+            i = skip_line(input, i + 4);
+            let content = content.iter().collect();
+            let block = match condition_id.strip_prefix('?') {
+                Some(param) if is_identifier(param.trim_start()) => {
+                    SqlBlock::Optional(param.trim_start().to_string(), content)
+                }
+                Some(_) => return None,
+                None => SqlBlock::Conditional(condition_id, content),
+            };
+            return Some((block, i));
+        }
+        i += 1;
+    }
+
+    None
+}
+
+fn parse_loop_block(input: &[char], start: usize) -> Option<(SqlBlock, usize)> {
+    let header_start = start + LOOP_OPEN.len(); // Skip "--~for "
+    let mut i = skip_line(input, header_start);
+
+    if i >= input.len() {
+        return None;
+    }
+
+    // Expect header in a form of "<var> in <collection>"
+    let header: String = input[header_start..i].iter().collect();
+    let (var, collection) = match header.split_whitespace().collect::<Vec<_>>()[..] {
+        [var, "in", collection] if is_identifier(var) && is_identifier(collection) => {
+            (var.to_string(), collection.to_string())
+        }
+        _ => return None,
+    };
+
+    let body_start = i;
+    let mut body_end = None;
+    let mut separator = DEFAULT_SEPARATOR.to_string();
+
+    // Find the closing "--~end", optionally preceded by "--~join <separator>"
+    while i < input.len() {
+        if is_at_newline_or_start(input, i) {
+            if body_end.is_none() && matches_pattern(input, i, LOOP_JOIN) {
+                let line_end = skip_line(input, i + LOOP_JOIN.len());
+                separator = trim_slice(&input[i + LOOP_JOIN.len()..line_end])
+                    .iter()
+                    .collect();
+                body_end = Some(i);
+                i = line_end;
+                continue;
+            }
+            if matches_pattern(input, i, LOOP_CLOSE) {
+                let body = trim_slice(&input[body_start..body_end.unwrap_or(i)]);
+                return Some((
+                    SqlBlock::Loop {
+                        var,
+                        collection,
+                        body: body.iter().collect(),
+                        separator,
+                    },
+                    skip_line(input, i + LOOP_CLOSE.len()),
+                ));
             }
-            return Some((condition_id, content.iter().collect(), i));
         }
         i += 1;
     }
@@ -67,20 +207,123 @@ fn parse_conditional_block(input: &[char], start: usize) -> Option<(String, Stri
     None
 }
 
-pub(crate) fn parse_sql_blocks(input: &[char]) -> Vec<SqlBlock> {
+fn parse_region_block(input: &[char], start: usize, opening: &[char]) -> Option<(SqlBlock, usize)> {
+    let i = skip_line(input, start + opening.len());
+
+    // Region opening line should contain nothing more than "--~where" or "--~set"
+    if !trim_slice(&input[start + opening.len()..i]).is_empty() {
+        return None;
+    }
+    let (blocks, end_pos) = parse_blocks(input, i, Some(REGION_CLOSE))?;
+    let block = if opening == WHERE_OPEN {
+        SqlBlock::Where(blocks)
+    } else {
+        SqlBlock::Set(blocks)
+    };
+    Some((block, skip_line(input, end_pos + REGION_CLOSE.len())))
+}
+
+fn parse_cfg_block(input: &[char], start: usize) -> Option<(SqlBlock, usize)> {
+    let i = skip_line(input, start + CFG_OPEN.len());
+
+    // Predicate is everything within the outermost parentheses
+    let header: String = trim_slice(&input[start + CFG_OPEN.len() - 1..i])
+        .iter()
+        .collect();
+    let predicate = header
+        .strip_prefix('(')?
+        .strip_suffix(')')?
+        .trim()
+        .to_string();
+    if predicate.is_empty() {
+        return None;
+    }
+    let (blocks, end_pos) = parse_blocks(input, i, Some(REGION_CLOSE))?;
+    Some((
+        SqlBlock::Cfg(predicate, blocks),
+        skip_line(input, end_pos + REGION_CLOSE.len()),
+    ))
+}
+
+fn parse_include(input: &[char], start: usize) -> Option<(SqlBlock, usize)> {
+    let i = skip_line(input, start + INCLUDE.len());
+    let name: String = trim_slice(&input[start + INCLUDE.len()..i])
+        .iter()
+        .collect();
+
+    is_identifier(&name).then_some((SqlBlock::Include(name), i))
+}
+
+fn parse_order_by(input: &[char], start: usize) -> Option<(SqlBlock, usize)> {
+    let i = skip_line(input, start + ORDER_BY.len());
+    let header: String = input[start + ORDER_BY.len()..i].iter().collect();
+
+    // Expect header in a form of "<name> { <column>, ... } [default <column>]"
+    let (name, rest) = header.split_once('{')?;
+    let (columns, rest) = rest.split_once('}')?;
+    let name = name.trim().to_string();
+    let columns: Vec<String> = columns.split(',').map(|c| c.trim().to_string()).collect();
+    let default = match rest.split_whitespace().collect::<Vec<_>>()[..] {
+        [] => columns.first()?.clone(),
+        ["default", column] => column.to_string(),
+        _ => return None,
+    };
+    let valid = is_identifier(&name)
+        && columns.iter().all(|column| is_identifier(column))
+        && columns.contains(&default);
+
+    valid.then_some((
+        SqlBlock::OrderBy {
+            name,
+            columns,
+            default,
+        },
+        i,
+    ))
+}
+
+/// Parses blocks starting at `start` position up to the end of input, or up to the line
+/// starting with `terminator` if provided. Returns parsed blocks along with position of
+/// the terminator (or end of input).
+fn parse_blocks(
+    input: &[char],
+    start: usize,
+    terminator: Option<&[char]>,
+) -> Option<(Vec<SqlBlock>, usize)> {
     let mut result = Vec::with_capacity(3);
-    let mut i = 0;
-    let mut literal_start = 0;
+    let mut i = start;
+    let mut literal_start = start;
+    let mut end = None;
+
     while i < input.len() {
-        if is_at_newline_or_start(input, i) && matches_pattern(input, i, BLOCK_OPEN) {
-            if i > literal_start {
+        if is_at_newline_or_start(input, i) {
+            if terminator.is_some_and(|t| matches_pattern(input, i, t)) {
+                end = Some(i);
+                break;
+            }
+            let block = if matches_pattern(input, i, BLOCK_OPEN) {
+                parse_conditional_block(input, i)
+            } else if matches_pattern(input, i, LOOP_OPEN) {
+                parse_loop_block(input, i)
+            } else if matches_pattern(input, i, WHERE_OPEN) {
+                parse_region_block(input, i, WHERE_OPEN)
+            } else if matches_pattern(input, i, SET_OPEN) {
+                parse_region_block(input, i, SET_OPEN)
+            } else if matches_pattern(input, i, CFG_OPEN) {
+                parse_cfg_block(input, i)
+            } else if matches_pattern(input, i, INCLUDE) {
+                parse_include(input, i)
+            } else if matches_pattern(input, i, ORDER_BY) {
+                parse_order_by(input, i)
+            } else {
+                None
+            };
+            if let Some((block, end_pos)) = block {
                 let literal = trim_slice(&input[literal_start..i]);
                 if !literal.is_empty() {
                     result.push(SqlBlock::Literal(literal.iter().collect()));
                 }
-            }
-            if let Some((condition_id, content, end_pos)) = parse_conditional_block(input, i) {
-                result.push(SqlBlock::Conditional(condition_id, content));
+                result.push(block);
                 i = end_pos;
                 literal_start = i;
                 continue;
@@ -88,18 +331,535 @@ pub(crate) fn parse_sql_blocks(input: &[char]) -> Vec<SqlBlock> {
         }
         i += 1;
     }
+    if terminator.is_some() && end.is_none() {
+        return None;
+    }
 
     // Add any remaining literal content
-    if literal_start < input.len() {
-        let literal = trim_slice(&input[literal_start..input.len()]);
+    let end = end.unwrap_or(input.len());
+    if literal_start < end {
+        let literal = trim_slice(&input[literal_start..end]);
         if !literal.is_empty() {
             result.push(SqlBlock::Literal(literal.iter().collect()));
         }
     }
 
+    Some((result, end))
+}
+
+pub(crate) fn parse_sql_blocks(input: &[char]) -> Vec<SqlBlock> {
+    parse_blocks(input, 0, None)
+        .map(|(blocks, _)| blocks)
+        .unwrap_or_default()
+}
+
+/// Replaces `--~include` directives with blocks of corresponding snippets, recursively.
+/// Panics if snippet is unknown or includes itself, either directly or indirectly.
+pub(crate) fn expand_includes(
+    blocks: &[SqlBlock],
+    snippets: &BTreeMap<String, Vec<SqlBlock>>,
+) -> Vec<SqlBlock> {
+    expand_blocks(blocks, snippets, &mut Vec::new(), true)
+}
+
+/// Expands blocks, with `including` holding names of snippets being currently expanded.
+/// Adjacent literals get merged unless blocks make parts of a `WHERE` or `SET` clause.
+fn expand_blocks(
+    blocks: &[SqlBlock],
+    snippets: &BTreeMap<String, Vec<SqlBlock>>,
+    including: &mut Vec<String>,
+    merge: bool,
+) -> Vec<SqlBlock> {
+    let mut result: Vec<SqlBlock> = Vec::with_capacity(blocks.len());
+    let mut push = |block: SqlBlock| match (result.last_mut(), block) {
+        (Some(SqlBlock::Literal(sql)), SqlBlock::Literal(literal)) if merge => {
+            sql.push('\n');
+            sql.push_str(&literal);
+        }
+        (_, block) => result.push(block),
+    };
+    for block in blocks {
+        match block {
+            SqlBlock::Include(name) => {
+                if including.contains(name) {
+                    panic!(
+                        "Cyclic inclusion of snippet {}: {} -> {}",
+                        name,
+                        including.join(" -> "),
+                        name
+                    );
+                }
+                let snippet = snippets
+                    .get(name)
+                    .unwrap_or_else(|| panic!("Included snippet {} is not defined", name));
+
+                including.push(name.clone());
+                expand_blocks(snippet, snippets, including, merge)
+                    .into_iter()
+                    .for_each(&mut push);
+                including.pop();
+            }
+            SqlBlock::Where(blocks) => {
+                push(SqlBlock::Where(expand_blocks(
+                    blocks, snippets, including, false,
+                )));
+            }
+            SqlBlock::Set(blocks) => {
+                push(SqlBlock::Set(expand_blocks(
+                    blocks, snippets, including, false,
+                )));
+            }
+            SqlBlock::Cfg(predicate, blocks) => {
+                push(SqlBlock::Cfg(
+                    predicate.clone(),
+                    expand_blocks(blocks, snippets, including, true),
+                ));
+            }
+            block => push(block.clone()),
+        }
+    }
     result
 }
 
+/// Checks if any of blocks contains a `{{:name}}` slot of identifier passed at runtime.
+pub(crate) fn has_identifier_slots(blocks: &[SqlBlock]) -> bool {
+    blocks.iter().any(|block| match block {
+        SqlBlock::Literal(sql)
+        | SqlBlock::Conditional(_, sql)
+        | SqlBlock::Optional(_, sql)
+        | SqlBlock::Loop { body: sql, .. } => split_slots(sql)
+            .iter()
+            .any(|e| matches!(e, Expansion::Slot(_))),
+        SqlBlock::Where(blocks) | SqlBlock::Set(blocks) | SqlBlock::Cfg(_, blocks) => {
+            has_identifier_slots(blocks)
+        }
+        SqlBlock::Include(_) | SqlBlock::OrderBy { .. } => false,
+    })
+}
+
+/// Renders blocks into SQL, the same way generated code does it at runtime, including
+/// blocks for which `included` condition returns true. Returns `None` if SQL depends
+/// on runtime values other than block conditions, or if it's an update with no columns
+/// to set, which generated code reports as an error at runtime.
+pub(crate) fn render_sql(
+    blocks: &[SqlBlock],
+    included: &dyn Fn(Condition) -> bool,
+) -> Option<String> {
+    let mut sql = String::new();
+    for block in blocks {
+        match block {
+            SqlBlock::Literal(content) | SqlBlock::Conditional(_, content)
+                if has_expansions(content) =>
+            {
+                return None
+            }
+            SqlBlock::Literal(literal) => {
+                sql.push('\n');
+                sql.push_str(literal);
+            }
+            SqlBlock::Conditional(id, content) => {
+                if included(Condition::Block(id)) {
+                    sql.push('\n');
+                    sql.push_str(content);
+                }
+            }
+            SqlBlock::Where(blocks) | SqlBlock::Set(blocks) => {
+                let parts = blocks
+                    .iter()
+                    .map(|block| render_sql(std::slice::from_ref(block), included))
+                    .collect::<Option<Vec<_>>>()?;
+                let pushed = match block {
+                    SqlBlock::Where(_) => Clause::Where.push(&mut sql, &parts),
+                    _ => Clause::Set.push(&mut sql, &parts),
+                };
+                if !pushed && matches!(block, SqlBlock::Set(_)) {
+                    return None;
+                }
+            }
+            SqlBlock::Cfg(predicate, blocks) => {
+                if included(Condition::Cfg(predicate)) {
+                    sql.push_str(&render_sql(blocks, included)?);
+                }
+            }
+            SqlBlock::Optional(..)
+            | SqlBlock::Loop { .. }
+            | SqlBlock::Include(_)
+            | SqlBlock::OrderBy { .. } => return None,
+        }
+    }
+    Some(sql)
+}
+
+/// Splits SQL fragment into verbatim parts and `:name` bind markers, where `name` is one of
+/// given `names`. Markers inside quoted strings, comments and Postgres `::type` casts are
+/// left intact.
+pub(crate) fn split_binds(sql: &str, names: &[&str]) -> Vec<Fragment> {
+    split_markers(sql, Fragment::Sql, |marker| {
+        let (name, len) = marker_name(marker.strip_prefix(&[':'])?);
+        names
+            .contains(&name.as_str())
+            .then_some((Fragment::Bind(name), 1 + len))
+    })
+}
+
+/// Splits SQL fragment into verbatim parts and markers expanded at runtime: `:v*:name`
+/// value lists, `:t*:name` tuple lists, `:i:name` identifiers and `{{:name}}` slots.
+pub(crate) fn split_expansions(sql: &str) -> Vec<Expansion> {
+    split_markers(sql, Expansion::Sql, |marker| {
+        if let Some(slot) = slot(marker) {
+            return Some(slot);
+        }
+        let marker = marker.strip_prefix(&[':'])?;
+        let (prefix, expansion): (_, fn(String) -> Expansion) = if marker.starts_with(VALUE_LIST) {
+            (VALUE_LIST, Expansion::ValueList)
+        } else if marker.starts_with(IDENTIFIER) {
+            (IDENTIFIER, Expansion::Identifier)
+        } else if marker.starts_with(TUPLE_LIST) {
+            (TUPLE_LIST, Expansion::TupleList)
+        } else {
+            return None;
+        };
+        let (name, len) = marker_name(&marker[prefix.len()..]);
+        is_identifier(&name).then_some((expansion(name), 1 + prefix.len() + len))
+    })
+}
+
+/// Splits SQL fragment into verbatim parts and `{{:name}}` slots only.
+pub(crate) fn split_slots(sql: &str) -> Vec<Expansion> {
+    split_markers(sql, Expansion::Sql, slot)
+}
+
+/// Recognizes `{{:name}}` slot at the beginning of a marker. Unterminated slot is no slot
+/// at all.
+fn slot(marker: &[char]) -> Option<(Expansion, usize)> {
+    let rest = marker.strip_prefix(IDENTIFIER_SLOT)?;
+    let end = rest.windows(2).position(|w| w == SLOT_CLOSE)?;
+    let name: String = rest[..end].iter().collect();
+    let name = name.trim();
+    is_identifier(name).then(|| {
+        let len = IDENTIFIER_SLOT.len() + end + SLOT_CLOSE.len();
+        (Expansion::Slot(name.to_string()), len)
+    })
+}
+
+/// Checks if SQL fragment contains any marker expanded at runtime.
+pub(crate) fn has_expansions(sql: &str) -> bool {
+    split_expansions(sql)
+        .iter()
+        .any(|e| !matches!(e, Expansion::Sql(_)))
+}
+
+/// Checks if a positional `?` placeholder follows a parameter bound at runtime, like
+/// elements of a value list or an optional parameter. These are bound after all the regular
+/// query parameters, so they would get mixed up.
+pub(crate) fn has_placeholders_after_binds(blocks: &[SqlBlock]) -> bool {
+    placeholders_after_binds(blocks, &mut false)
+}
+
+fn placeholders_after_binds(blocks: &[SqlBlock], bound: &mut bool) -> bool {
+    blocks.iter().any(|block| match block {
+        SqlBlock::Literal(sql) | SqlBlock::Conditional(_, sql) => {
+            fragment_placeholders_after_binds(sql, &[], bound)
+        }
+        SqlBlock::Optional(param, sql) => fragment_placeholders_after_binds(sql, &[param], bound),
+        // Body gets repeated, so its placeholders follow binds of previous repetitions
+        SqlBlock::Loop { var, body, .. } => {
+            let binds = split_expansions(body).into_iter().any(|e| match e {
+                Expansion::Sql(sql) => split_binds(&sql, &[var])
+                    .iter()
+                    .any(|f| matches!(f, Fragment::Bind(_))),
+                Expansion::ValueList(_) | Expansion::TupleList(_) => true,
+                Expansion::Identifier(_) | Expansion::Slot(_) => false,
+            });
+            let placeholders = has_placeholders(body);
+            *bound |= binds;
+            *bound && placeholders
+        }
+        SqlBlock::Where(blocks) | SqlBlock::Set(blocks) | SqlBlock::Cfg(_, blocks) => {
+            placeholders_after_binds(blocks, bound)
+        }
+        SqlBlock::Include(_) | SqlBlock::OrderBy { .. } => false,
+    })
+}
+
+/// Checks if a placeholder of SQL fragment follows a parameter bound at runtime, either
+/// a collection expanded in place of a marker or one of `names` bound at `:name` marker.
+fn fragment_placeholders_after_binds(sql: &str, names: &[&str], bound: &mut bool) -> bool {
+    split_expansions(sql).into_iter().any(|e| match e {
+        Expansion::Sql(sql) => split_binds(&sql, names).into_iter().any(|f| match f {
+            Fragment::Sql(sql) => *bound && has_placeholders(&sql),
+            Fragment::Bind(_) => {
+                *bound = true;
+                false
+            }
+        }),
+        Expansion::ValueList(_) | Expansion::TupleList(_) => {
+            *bound = true;
+            false
+        }
+        Expansion::Identifier(_) | Expansion::Slot(_) => false,
+    })
+}
+
+/// Checks if SQL fragment contains `?` (or sqlite's `$1`) placeholder outside of quoted
+/// strings and comments.
+fn has_placeholders(sql: &str) -> bool {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        if let Some(end) = skip_quoted(&chars, i) {
+            i = end;
+        } else if chars[i] == '?'
+            || (chars[i] == '$' && chars.get(i + 1).is_some_and(char::is_ascii_digit))
+        {
+            return true;
+        } else {
+            i += 1;
+        }
+    }
+    false
+}
+
+/// Checks if statement made of given blocks inserts rows, i.e. it's an `INSERT` or `REPLACE`
+/// one, possibly preceded by a `WITH` clause.
+pub(crate) fn is_insert(blocks: &[SqlBlock]) -> bool {
+    let chars: Vec<char> = sql_text(blocks).chars().collect();
+    let mut words = Vec::new();
+    let mut depth = 0usize;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if let Some(end) = skip_quoted(&chars, i) {
+            i = end;
+        } else if c.is_alphabetic() || c == '_' {
+            let (word, len) = marker_name(&chars[i..]);
+            if depth == 0 {
+                words.push(word.to_uppercase());
+            }
+            i += len;
+        } else {
+            match c {
+                '(' => depth += 1,
+                ')' => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            i += 1;
+        }
+    }
+    let statement = match words.first().map(String::as_str) {
+        // Common table expressions are parenthesized, so they don't get in the way
+        Some("WITH") => words.iter().find(|w| {
+            matches!(
+                w.as_str(),
+                "SELECT" | "INSERT" | "REPLACE" | "UPDATE" | "DELETE" | "VALUES"
+            )
+        }),
+        _ => words.first(),
+    };
+    statement.is_some_and(|w| w == "INSERT" || w == "REPLACE")
+}
+
+/// Concatenates SQL of given blocks, no matter if they get included or not.
+fn sql_text(blocks: &[SqlBlock]) -> String {
+    blocks
+        .iter()
+        .map(|block| match block {
+            SqlBlock::Literal(sql) | SqlBlock::Conditional(_, sql) | SqlBlock::Optional(_, sql) => {
+                sql.clone()
+            }
+            SqlBlock::Loop { body, .. } => body.clone(),
+            SqlBlock::Where(blocks) | SqlBlock::Set(blocks) | SqlBlock::Cfg(_, blocks) => {
+                sql_text(blocks)
+            }
+            SqlBlock::Include(_) | SqlBlock::OrderBy { .. } => String::new(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Collects names of `:t*:name` tuple lists across given blocks.
+pub(crate) fn tuple_lists(blocks: &[SqlBlock]) -> Vec<String> {
+    blocks
+        .iter()
+        .flat_map(|block| match block {
+            SqlBlock::Literal(sql) | SqlBlock::Conditional(_, sql) | SqlBlock::Optional(_, sql) => {
+                split_expansions(sql)
+                    .into_iter()
+                    .filter_map(|e| match e {
+                        Expansion::TupleList(name) => Some(name),
+                        _ => None,
+                    })
+                    .collect()
+            }
+            SqlBlock::Where(blocks) | SqlBlock::Set(blocks) | SqlBlock::Cfg(_, blocks) => {
+                tuple_lists(blocks)
+            }
+            // Tuple lists are not allowed to repeat
+            SqlBlock::Loop { .. } | SqlBlock::Include(_) | SqlBlock::OrderBy { .. } => Vec::new(),
+        })
+        .collect()
+}
+
+/// Checks if any of blocks is a conditional one, resolved at runtime.
+pub(crate) fn has_conditionals(blocks: &[SqlBlock]) -> bool {
+    blocks.iter().any(|block| match block {
+        SqlBlock::Conditional(..) => true,
+        SqlBlock::Where(blocks) | SqlBlock::Set(blocks) | SqlBlock::Cfg(_, blocks) => {
+            has_conditionals(blocks)
+        }
+        _ => false,
+    })
+}
+
+/// Collects `--~order_by` blocks across given blocks.
+pub(crate) fn order_bys(blocks: &[SqlBlock]) -> Vec<&SqlBlock> {
+    blocks
+        .iter()
+        .flat_map(|block| match block {
+            SqlBlock::OrderBy { .. } => vec![block],
+            SqlBlock::Where(blocks) | SqlBlock::Set(blocks) | SqlBlock::Cfg(_, blocks) => {
+                order_bys(blocks)
+            }
+            _ => Vec::new(),
+        })
+        .collect()
+}
+
+/// Returns name at the beginning of a marker along with its length.
+fn marker_name(marker: &[char]) -> (String, usize) {
+    let len = marker
+        .iter()
+        .position(|c| !(c.is_alphanumeric() || *c == '_'))
+        .unwrap_or(marker.len());
+    (marker[..len].iter().collect(), len)
+}
+
+/// Splits SQL fragment at markers starting with a colon or a brace, outside of quoted
+/// strings, identifiers, comments and Postgres `::type` casts. For input starting at the
+/// colon or brace, `marker` returns a piece made of recognized marker along with marker
+/// length. Verbatim parts are made by `literal`.
+fn split_markers<T>(
+    sql: &str,
+    literal: impl Fn(String) -> T,
+    marker: impl Fn(&[char]) -> Option<(T, usize)>,
+) -> Vec<T> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut result = Vec::new();
+    let mut verbatim = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if let Some(end) = skip_quoted(&chars, i) {
+            verbatim.extend(&chars[i..end]);
+            i = end;
+            continue;
+        } else if (c == ':' && (i == 0 || chars[i - 1] != ':')) || c == '{' {
+            if let Some((piece, len)) = marker(&chars[i..]) {
+                if !verbatim.is_empty() {
+                    result.push(literal(std::mem::take(&mut verbatim)));
+                }
+                result.push(piece);
+                i += len;
+                continue;
+            }
+        }
+        verbatim.push(c);
+        i += 1;
+    }
+    if !verbatim.is_empty() {
+        result.push(literal(verbatim));
+    }
+    result
+}
+
+/// Returns position right after the end of quoted string, comment or dollar-quoted body
+/// starting at `start`, if there is one.
+pub(crate) fn skip_quoted(chars: &[char], start: usize) -> Option<usize> {
+    let find = |from: usize, pattern: &[char]| {
+        (from..chars.len())
+            .find(|&i| matches_pattern(chars, i, pattern))
+            .map_or(chars.len(), |i| i + pattern.len())
+    };
+    match chars[start] {
+        q @ ('\'' | '"' | '`') => Some(find(start + 1, &[q])),
+        '-' if chars.get(start + 1) == Some(&'-') => Some(skip_line(chars, start)),
+        '/' if chars.get(start + 1) == Some(&'*') => Some(find(start + 2, &['*', '/'])),
+        // Postgres $tag$ quoting, not to be confused with $1 placeholder
+        '$' => {
+            let len = chars[start + 1..]
+                .iter()
+                .position(|c| !(c.is_alphanumeric() || *c == '_'))?;
+            let tag = &chars[start..start + len + 2];
+            (chars.get(start + len + 1) == Some(&'$') && !chars[start + 1].is_ascii_digit())
+                .then(|| find(start + tag.len(), tag))
+        }
+        _ => None,
+    }
+}
+
+/// Splits SQL script into statements separated by semicolons, which are not a part of quoted
+/// string, comment or trigger body. Statements made of comments only are skipped.
+pub(crate) fn split_statements(sql: &str) -> Vec<String> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut statements = Vec::new();
+    let mut start = 0;
+    let mut words = Vec::new();
+    let mut code = false;
+    let mut depth = 0usize;
+    let mut i = 0;
+
+    let mut push = |start: usize, end: usize, code: bool| {
+        let statement = trim_slice(&chars[start..end]);
+        if code {
+            statements.push(statement.iter().collect::<String>());
+        }
+    };
+    while i < chars.len() {
+        let c = chars[i];
+        if let Some(end) = skip_quoted(&chars, i) {
+            code |= !matches!(c, '-' | '/');
+            i = end;
+        } else if c.is_alphabetic() || c == '_' {
+            let (word, len) = marker_name(&chars[i..]);
+            let word = word.to_uppercase();
+            // Trigger body is a block of statements, possibly with nested CASE expressions
+            let trigger = words.first().is_some_and(|w| w == "CREATE")
+                && words.iter().any(|w| w == "TRIGGER");
+            if trigger && words.last().is_none_or(|w| w != "END") {
+                match word.as_str() {
+                    "BEGIN" | "CASE" => depth += 1,
+                    "END" => {
+                        let next = chars[i + len..]
+                            .iter()
+                            .position(|c| !c.is_whitespace())
+                            .map(|pos| marker_name(&chars[i + len + pos..]).0.to_uppercase());
+                        // Blocks closed by END IF and the likes were not counted in
+                        if !matches!(next.as_deref(), Some("IF" | "LOOP" | "WHILE" | "REPEAT")) {
+                            depth = depth.saturating_sub(1);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            words.push(word);
+            code = true;
+            i += len;
+        } else if c == ';' && depth == 0 {
+            push(start, i, code);
+            start = i + 1;
+            words.clear();
+            code = false;
+            i += 1;
+        } else {
+            code |= !c.is_whitespace();
+            i += 1;
+        }
+    }
+    push(start, chars.len(), code);
+    statements
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -167,4 +927,531 @@ SELECT 1
             SqlBlock::Conditional("test".to_string(), "SELECT 1".to_string())
         );
     }
+
+    #[test]
+    fn test_loop_blocks() {
+        let input = r#"
+SELECT name FROM tags
+WHERE
+--~for tag in tags
+name LIKE :tag
+--~join OR
+--~end
+ORDER BY name"#;
+
+        let chars: Vec<char> = input.chars().collect();
+        let result = parse_sql_blocks(&chars);
+
+        assert_eq!(result.len(), 3);
+        assert_eq!(
+            result[0],
+            SqlBlock::Literal("SELECT name FROM tags\nWHERE".to_string())
+        );
+        assert_eq!(
+            result[1],
+            SqlBlock::Loop {
+                var: "tag".to_string(),
+                collection: "tags".to_string(),
+                body: "name LIKE :tag".to_string(),
+                separator: "OR".to_string(),
+            }
+        );
+        assert_eq!(result[2], SqlBlock::Literal("ORDER BY name".to_string()));
+    }
+
+    #[test]
+    fn test_loop_default_separator() {
+        let input = r#"SELECT * FROM users WHERE user_id IN (
+--~for id in ids
+:id
+--~end
+)"#;
+
+        let chars: Vec<char> = input.chars().collect();
+        let result = parse_sql_blocks(&chars);
+
+        assert_eq!(result.len(), 3);
+        assert_eq!(
+            result[1],
+            SqlBlock::Loop {
+                var: "id".to_string(),
+                collection: "ids".to_string(),
+                body: ":id".to_string(),
+                separator: ",".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_split_binds() {
+        let result = split_binds(
+            "(name LIKE :tag OR email LIKE :tag) AND ':tag' = :other::text",
+            &["tag", "other"],
+        );
+
+        assert_eq!(
+            result,
+            vec![
+                Fragment::Sql("(name LIKE ".to_string()),
+                Fragment::Bind("tag".to_string()),
+                Fragment::Sql(" OR email LIKE ".to_string()),
+                Fragment::Bind("tag".to_string()),
+                Fragment::Sql(") AND ':tag' = ".to_string()),
+                Fragment::Bind("other".to_string()),
+                Fragment::Sql("::text".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_loop_body_with_apostrophe_in_comment() {
+        let input = r#"SELECT name FROM tags WHERE
+--~for tag in tags
+-- user's tag
+name LIKE :tag
+--~join OR
+--~end"#;
+
+        let chars: Vec<char> = input.chars().collect();
+        let result = parse_sql_blocks(&chars);
+        let SqlBlock::Loop { var, body, .. } = &result[1] else {
+            panic!("expected loop block, got {:?}", result[1]);
+        };
+
+        assert_eq!(
+            split_binds(body, &[var]),
+            vec![
+                Fragment::Sql("-- user's tag\nname LIKE ".to_string()),
+                Fragment::Bind("tag".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_where_and_set_regions() {
+        let input = r#"
+UPDATE users
+--~set
+--~{ name
+name = $2,
+--~}
+--~{ email
+email = $3
+--~}
+--~end
+--~where
+user_id = $1
+--~for id in ids
+:id
+--~end
+--~end"#;
+
+        let chars: Vec<char> = input.chars().collect();
+        let result = parse_sql_blocks(&chars);
+
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0], SqlBlock::Literal("UPDATE users".to_string()));
+        assert_eq!(
+            result[1],
+            SqlBlock::Set(vec![
+                SqlBlock::Conditional("name".to_string(), "name = $2,".to_string()),
+                SqlBlock::Conditional("email".to_string(), "email = $3".to_string()),
+            ])
+        );
+        assert_eq!(
+            result[2],
+            SqlBlock::Where(vec![
+                SqlBlock::Literal("user_id = $1".to_string()),
+                SqlBlock::Loop {
+                    var: "id".to_string(),
+                    collection: "ids".to_string(),
+                    body: ":id".to_string(),
+                    separator: ",".to_string(),
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_optional_blocks() {
+        let input = r#"SELECT * FROM users
+WHERE 1=1
+--~{ ?email
+AND email = :email
+--~}
+--~{ verified
+AND verified
+--~}"#;
+
+        let chars: Vec<char> = input.chars().collect();
+        let result = parse_sql_blocks(&chars);
+
+        assert_eq!(result.len(), 3);
+        assert_eq!(
+            result[1],
+            SqlBlock::Optional("email".to_string(), "AND email = :email".to_string())
+        );
+        assert_eq!(
+            result[2],
+            SqlBlock::Conditional("verified".to_string(), "AND verified".to_string())
+        );
+    }
+
+    #[test]
+    fn test_render_sql() {
+        let input = r#"SELECT * FROM users
+--~where
+--~{ by_name
+AND name = ?
+--~}
+--~{ by_email
+AND email = ?
+--~}
+--~end"#;
+
+        let chars: Vec<char> = input.chars().collect();
+        let blocks = parse_sql_blocks(&chars);
+
+        assert_eq!(
+            render_sql(&blocks, &|c| c == Condition::Block("by_email")),
+            Some("\nSELECT * FROM users\nWHERE (email = ?)".to_string())
+        );
+        assert_eq!(
+            render_sql(&blocks, &|_| false),
+            Some("\nSELECT * FROM users".to_string())
+        );
+    }
+
+    #[test]
+    fn test_render_empty_set_region() {
+        let input = r#"UPDATE users
+--~set
+--~{ by_name
+name = ?,
+--~}
+--~end
+WHERE user_id = 1"#;
+
+        let chars: Vec<char> = input.chars().collect();
+        let blocks = parse_sql_blocks(&chars);
+
+        assert_eq!(
+            render_sql(&blocks, &|_| true),
+            Some("\nUPDATE users\nSET name = ?\nWHERE user_id = 1".to_string())
+        );
+        assert_eq!(render_sql(&blocks, &|_| false), None);
+    }
+
+    #[test]
+    fn test_render_alternatives_in_where_region() {
+        let input = r#"SELECT * FROM users
+--~where
+--~{ by_status
+status = 'active' OR status = 'pending'
+--~}
+--~{ recent
+AND id < 3
+--~}
+--~end"#;
+
+        let chars: Vec<char> = input.chars().collect();
+        let blocks = parse_sql_blocks(&chars);
+
+        assert_eq!(
+            render_sql(&blocks, &|_| true),
+            Some(
+                "\nSELECT * FROM users\nWHERE (status = 'active' OR status = 'pending')\nAND (id < 3)"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_render_runtime_sql() {
+        let input = r#"SELECT * FROM users
+--~{ ?email
+WHERE email = :email
+--~}"#;
+
+        let chars: Vec<char> = input.chars().collect();
+        assert_eq!(render_sql(&parse_sql_blocks(&chars), &|_| true), None);
+    }
+
+    #[test]
+    fn test_cfg_blocks() {
+        let input = r#"SELECT * FROM users
+--~cfg(feature = "audit")
+JOIN audit_log USING (user_id)
+--~{ recent
+WHERE logged_at > NOW() - INTERVAL '1 day'
+--~}
+--~end"#;
+
+        let chars: Vec<char> = input.chars().collect();
+        let blocks = parse_sql_blocks(&chars);
+
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(
+            blocks[1],
+            SqlBlock::Cfg(
+                "feature = \"audit\"".to_string(),
+                vec![
+                    SqlBlock::Literal("JOIN audit_log USING (user_id)".to_string()),
+                    SqlBlock::Conditional(
+                        "recent".to_string(),
+                        "WHERE logged_at > NOW() - INTERVAL '1 day'".to_string()
+                    ),
+                ]
+            )
+        );
+        assert_eq!(
+            render_sql(&blocks, &|c| c != Condition::Cfg("feature = \"audit\"")),
+            Some("\nSELECT * FROM users".to_string())
+        );
+    }
+
+    #[test]
+    fn test_include_in_where_region() {
+        let input = r#"SELECT * FROM users
+--~where
+--~include active
+--~{ by_email
+email = ?
+--~}
+--~end"#;
+
+        let chars: Vec<char> = input.chars().collect();
+        let blocks = parse_sql_blocks(&chars);
+        let snippets = BTreeMap::from([(
+            "active".to_string(),
+            vec![
+                SqlBlock::Literal("active".to_string()),
+                SqlBlock::Literal("NOT deleted".to_string()),
+            ],
+        )]);
+
+        assert_eq!(
+            blocks[1],
+            SqlBlock::Where(vec![
+                SqlBlock::Include("active".to_string()),
+                SqlBlock::Conditional("by_email".to_string(), "email = ?".to_string()),
+            ])
+        );
+        assert_eq!(
+            render_sql(&expand_includes(&blocks, &snippets), &|_| true),
+            Some(
+                "\nSELECT * FROM users\nWHERE (active)\nAND (NOT deleted)\nAND (email = ?)"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_order_by_blocks() {
+        let input = r#"SELECT * FROM users
+--~order_by sort { name, email, created_at } default created_at
+LIMIT 10"#;
+
+        let chars: Vec<char> = input.chars().collect();
+        let blocks = parse_sql_blocks(&chars);
+        assert_eq!(
+            blocks,
+            vec![
+                SqlBlock::Literal("SELECT * FROM users".to_string()),
+                SqlBlock::OrderBy {
+                    name: "sort".to_string(),
+                    columns: vec!["name".into(), "email".into(), "created_at".into()],
+                    default: "created_at".to_string(),
+                },
+                SqlBlock::Literal("LIMIT 10".to_string()),
+            ]
+        );
+        assert_eq!(render_sql(&blocks, &|_| true), None);
+
+        // Default column is the first one, unless it's given explicitly
+        let chars: Vec<char> = "--~order_by sort { name, email }".chars().collect();
+        assert!(matches!(
+            &parse_sql_blocks(&chars)[..],
+            [SqlBlock::OrderBy { default, .. }] if default == "name"
+        ));
+
+        // Default needs to be one of allowed columns
+        let chars: Vec<char> = "--~order_by sort { name } default email".chars().collect();
+        assert!(matches!(
+            &parse_sql_blocks(&chars)[..],
+            [SqlBlock::Literal(_)]
+        ));
+    }
+
+    #[test]
+    fn test_split_statements() {
+        let script = r#"
+CREATE TABLE users (name TEXT DEFAULT 'a;b'); -- trailing; comment
+/* comment; */ CREATE INDEX "idx;users" ON users(name);
+CREATE FUNCTION touch() RETURNS trigger AS $body$
+BEGIN
+  NEW.name := 'x'; RETURN NEW;
+END;
+$body$ LANGUAGE plpgsql;
+CREATE TRIGGER audit AFTER INSERT ON users
+BEGIN
+  INSERT INTO log VALUES (CASE WHEN NEW.name = $1 THEN 1 ELSE 2 END);
+  UPDATE users SET name = 'y';
+END;
+-- nothing but a comment
+"#;
+
+        assert_eq!(
+            split_statements(script),
+            vec![
+                "CREATE TABLE users (name TEXT DEFAULT 'a;b')",
+                "-- trailing; comment\n/* comment; */ CREATE INDEX \"idx;users\" ON users(name)",
+                "CREATE FUNCTION touch() RETURNS trigger AS $body$\nBEGIN\n  NEW.name := 'x'; RETURN NEW;\nEND;\n$body$ LANGUAGE plpgsql",
+                "CREATE TRIGGER audit AFTER INSERT ON users\nBEGIN\n  INSERT INTO log VALUES (CASE WHEN NEW.name = $1 THEN 1 ELSE 2 END);\n  UPDATE users SET name = 'y';\nEND",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_placeholders_after_binds() {
+        let blocks = |input: &str| parse_sql_blocks(&input.chars().collect::<Vec<_>>());
+
+        assert!(!has_placeholders_after_binds(&blocks(
+            "SELECT * FROM users WHERE status = ? AND user_id IN (:v*:ids)"
+        )));
+        assert!(!has_placeholders_after_binds(&blocks(
+            "SELECT * FROM users WHERE user_id IN (:v*:ids) AND name <> '?' -- why?"
+        )));
+        assert!(has_placeholders_after_binds(&blocks(
+            "SELECT * FROM users WHERE user_id IN (:v*:ids) AND status = ?"
+        )));
+        assert!(has_placeholders_after_binds(&blocks(
+            "SELECT * FROM users WHERE user_id IN (:v*:ids) AND status = $1"
+        )));
+        assert!(has_placeholders_after_binds(&blocks(
+            "SELECT * FROM users\n--~where\n--~{ by_ids\nuser_id IN (:v*:ids)\n--~}\nstatus = ?\n--~end"
+        )));
+        assert!(!has_placeholders_after_binds(&blocks(
+            "SELECT * FROM users WHERE user_id >= ?\n--~{ ?status\nAND status = :status\n--~}"
+        )));
+        assert!(has_placeholders_after_binds(&blocks(
+            "SELECT * FROM users WHERE 1=1\n--~{ ?status\nAND status = :status\n--~}\nAND user_id >= ?"
+        )));
+        assert!(has_placeholders_after_binds(&blocks(
+            "SELECT * FROM users WHERE\n--~for tag in tags\nname LIKE :tag AND user_id >= ?\n--~join OR\n--~end"
+        )));
+        assert!(has_placeholders_after_binds(&blocks(
+            "SELECT * FROM users WHERE 1=1\n--~{ ?status\nAND user_id IN (:v*:ids) AND status = ?\n--~}"
+        )));
+    }
+
+    #[test]
+    fn test_is_insert() {
+        let is_insert =
+            |input: &str| is_insert(&parse_sql_blocks(&input.chars().collect::<Vec<_>>()));
+
+        assert!(is_insert("INSERT INTO users(name) VALUES (?)"));
+        assert!(is_insert(
+            "-- new user\ninsert or replace into users(name) values (?)"
+        ));
+        assert!(is_insert("REPLACE INTO users(name) VALUES (?)"));
+        assert!(is_insert(
+            "WITH names AS (SELECT name FROM guests)\nINSERT INTO users(name) SELECT name FROM names"
+        ));
+        assert!(!is_insert(
+            "UPDATE users SET name = 'INSERT' WHERE user_id = ?"
+        ));
+        assert!(!is_insert(
+            "WITH ids AS (INSERT INTO log(id) VALUES (1) RETURNING id)\nDELETE FROM users"
+        ));
+        assert!(!is_insert("/* INSERT */ DELETE FROM users"));
+    }
+
+    #[test]
+    fn test_split_expansions() {
+        let sql = "SELECT * FROM users WHERE user_id IN (:v*:ids) AND name <> ':v*:names'\nORDER BY :i:column";
+        let insert = "INSERT INTO users VALUES :t*:rows";
+
+        assert_eq!(
+            split_expansions(sql),
+            vec![
+                Expansion::Sql("SELECT * FROM users WHERE user_id IN (".to_string()),
+                Expansion::ValueList("ids".to_string()),
+                Expansion::Sql(") AND name <> ':v*:names'\nORDER BY ".to_string()),
+                Expansion::Identifier("column".to_string()),
+            ]
+        );
+        assert_eq!(
+            split_expansions(insert),
+            vec![
+                Expansion::Sql("INSERT INTO users VALUES ".to_string()),
+                Expansion::TupleList("rows".to_string()),
+            ]
+        );
+        assert_eq!(
+            tuple_lists(&[SqlBlock::Where(vec![SqlBlock::Literal(insert.to_string())])]),
+            vec!["rows".to_string()]
+        );
+        assert_eq!(
+            render_sql(&[SqlBlock::Literal(sql.to_string())], &|_| true),
+            None
+        );
+    }
+
+    #[test]
+    fn test_split_expansions_skips_comments_and_identifiers() {
+        let sql = "SELECT user_id FROM users -- user's list\nWHERE user_id IN (:v*:ids)";
+        assert_eq!(
+            split_expansions(sql),
+            vec![
+                Expansion::Sql(
+                    "SELECT user_id FROM users -- user's list\nWHERE user_id IN (".to_string()
+                ),
+                Expansion::ValueList("ids".to_string()),
+                Expansion::Sql(")".to_string()),
+            ]
+        );
+
+        let sql = "SELECT /* user's */ \":v*:a\", `:v*:b` FROM users WHERE user_id IN (:v*:ids)";
+        assert_eq!(
+            split_expansions(sql),
+            vec![
+                Expansion::Sql(
+                    "SELECT /* user's */ \":v*:a\", `:v*:b` FROM users WHERE user_id IN ("
+                        .to_string()
+                ),
+                Expansion::ValueList("ids".to_string()),
+                Expansion::Sql(")".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_slots() {
+        let sql = "SELECT * FROM {{: schema }}.users -- {{:note}}\nWHERE name <> '{{:name}}' OR name = '{{:x'";
+
+        assert_eq!(
+            split_expansions(sql),
+            vec![
+                Expansion::Sql("SELECT * FROM ".to_string()),
+                Expansion::Slot("schema".to_string()),
+                Expansion::Sql(
+                    ".users -- {{:note}}\nWHERE name <> '{{:name}}' OR name = '{{:x'".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            split_slots("name LIKE :tag AND {{:x"),
+            vec![Expansion::Sql("name LIKE :tag AND {{:x".to_string())]
+        );
+        assert!(has_identifier_slots(&[SqlBlock::Loop {
+            var: "tag".to_string(),
+            collection: "tags".to_string(),
+            body: "{{:tags}}.name LIKE :tag".to_string(),
+            separator: "OR".to_string(),
+        }]));
+        assert!(!has_identifier_slots(&[SqlBlock::Literal(
+            "SELECT '{{:schema}}' -- {{:schema}}".to_string()
+        )]));
+    }
 }
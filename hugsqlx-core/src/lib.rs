@@ -2,18 +2,51 @@ extern crate proc_macro;
 
 mod condblock;
 mod parser;
+#[doc(hidden)]
+pub mod runtime;
+mod template;
 
-use parser::{Kind, Method, Query};
+use parser::{Affects, Fields, Kind, Method, Param, Query};
 use proc_macro2::{Ident, Span, TokenStream as TokenStream2};
-use quote::quote;
+use quote::{format_ident, quote};
 use std::{
-    collections::BTreeSet,
+    collections::{BTreeMap, BTreeSet},
     env, fs,
     path::{Path, PathBuf},
+    sync::OnceLock,
 };
-use syn::{parse_str, Lit, Meta, MetaNameValue, Type};
+use syn::{parse_str, Lit, Meta, MetaNameValue, NestedMeta, Type};
 
-pub struct Context(Type, Type, Type, Type);
+/// Maximal number of conditional blocks, for which all the SQL combinations get precomputed
+/// at compile time. Queries with more blocks have their SQL assembled at runtime.
+const MAX_PRECOMPUTED_BLOCKS: usize = 4;
+
+/// Maximal number of cfg predicates, for which a table of precomputed SQL combinations
+/// gets generated per combination of predicates. Only one of them is compiled in.
+const MAX_PRECOMPUTED_CFGS: usize = 3;
+
+/// Database specific pieces of generated code.
+pub struct Context {
+    db: Type,
+    args: Type,
+    row: Type,
+    result: Type,
+    /// Character quoting identifiers
+    quote: char,
+    /// Maximal number of parameters bound to a single statement
+    limit: usize,
+    /// Whether arrays can be bound as a single parameter
+    arrays: bool,
+    /// Whether server-side cursors can be declared
+    cursors: bool,
+    /// Whether placeholders are positional `?` ones, bound in order of appearance
+    positional: bool,
+    /// Code pushing SQL of a subquery returning no rows in place of given empty value list
+    empty_list: fn(&Ident) -> TokenStream2,
+    /// Expression of id of last inserted row (if any) obtained from query `result`.
+    /// Both sqlite and mysql report 0 when no row was inserted.
+    last_insert_id: TokenStream2,
+}
 pub enum ContextType {
     Postgres,
     Sqlite,
@@ -23,24 +56,56 @@ pub enum ContextType {
 impl Context {
     pub fn new(context_type: ContextType) -> Self {
         match context_type {
-            ContextType::Postgres => Context(
-                parse_str::<Type>("sqlx::postgres::Postgres").unwrap(),
-                parse_str::<Type>("sqlx::postgres::PgArguments").unwrap(),
-                parse_str::<Type>("sqlx::postgres::PgRow").unwrap(),
-                parse_str::<Type>("sqlx::postgres::PgQueryResult").unwrap(),
-            ),
-            ContextType::Sqlite => Context(
-                parse_str::<Type>("sqlx::sqlite::Sqlite").unwrap(),
-                parse_str::<Type>("sqlx::sqlite::SqliteArguments<'q>").unwrap(),
-                parse_str::<Type>("sqlx::sqlite::SqliteRow").unwrap(),
-                parse_str::<Type>("sqlx::sqlite::SqliteQueryResult").unwrap(),
-            ),
-            ContextType::Mysql => Context(
-                parse_str::<Type>("sqlx::mysql::MySql").unwrap(),
-                parse_str::<Type>("sqlx::mysql::MySqlArguments").unwrap(),
-                parse_str::<Type>("sqlx::mysql::MySqlRow").unwrap(),
-                parse_str::<Type>("sqlx::mysql::MySqlQueryResult").unwrap(),
-            ),
+            ContextType::Postgres => Context {
+                db: parse_str::<Type>("sqlx::postgres::Postgres").unwrap(),
+                args: parse_str::<Type>("sqlx::postgres::PgArguments").unwrap(),
+                row: parse_str::<Type>("sqlx::postgres::PgRow").unwrap(),
+                result: parse_str::<Type>("sqlx::postgres::PgQueryResult").unwrap(),
+                quote: '"',
+                limit: 65535,
+                arrays: true,
+                cursors: true,
+                positional: false,
+                // NULL of unknown type would be compared as text
+                empty_list: |items| {
+                    quote! {
+                        fn type_info<T: sqlx::Type<sqlx::postgres::Postgres>>(_: &[T]) -> sqlx::postgres::PgTypeInfo {
+                            T::type_info()
+                        }
+                        sql.push_str("SELECT NULL::");
+                        sql.push_str(sqlx::TypeInfo::name(&type_info(#items)));
+                        sql.push_str(" WHERE FALSE");
+                    }
+                },
+                last_insert_id: quote! { None },
+            },
+            ContextType::Sqlite => Context {
+                db: parse_str::<Type>("sqlx::sqlite::Sqlite").unwrap(),
+                args: parse_str::<Type>("sqlx::sqlite::SqliteArguments<'q>").unwrap(),
+                row: parse_str::<Type>("sqlx::sqlite::SqliteRow").unwrap(),
+                result: parse_str::<Type>("sqlx::sqlite::SqliteQueryResult").unwrap(),
+                quote: '"',
+                // Default of sqlite versions prior to 3.32, which might be linked
+                limit: 999,
+                arrays: false,
+                cursors: false,
+                positional: true,
+                empty_list: |_| quote! { sql.push_str("SELECT NULL WHERE 1=0"); },
+                last_insert_id: quote! { Some(result.last_insert_rowid()).filter(|id| *id != 0) },
+            },
+            ContextType::Mysql => Context {
+                db: parse_str::<Type>("sqlx::mysql::MySql").unwrap(),
+                args: parse_str::<Type>("sqlx::mysql::MySqlArguments").unwrap(),
+                row: parse_str::<Type>("sqlx::mysql::MySqlRow").unwrap(),
+                result: parse_str::<Type>("sqlx::mysql::MySqlQueryResult").unwrap(),
+                quote: '`',
+                limit: 65535,
+                arrays: false,
+                cursors: false,
+                positional: true,
+                empty_list: |_| quote! { sql.push_str("SELECT NULL FROM DUAL WHERE FALSE"); },
+                last_insert_id: quote! { i64::try_from(result.last_insert_id()).ok().filter(|id| *id != 0) },
+            },
             _ => panic!("None of [postgres, sqlite, mysql] feature enabled"),
         }
     }
@@ -61,7 +126,62 @@ fn find_attribute_values(ast: &syn::DeriveInput, attr_name: &str) -> Vec<String>
         .collect()
 }
 
+/// Find template variables defined by `#[hugsqlx(vars(name = "value", ...))]` attribute
+fn find_template_vars(ast: &syn::DeriveInput) -> BTreeMap<String, String> {
+    let mut vars = BTreeMap::new();
+    let nested = ast
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("hugsqlx"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(Meta::List(list)) => Some(list.nested),
+            _ => None,
+        })
+        .flatten();
+
+    for meta in nested {
+        match meta {
+            NestedMeta::Meta(Meta::List(list)) if list.path.is_ident("vars") => {
+                for var in list.nested {
+                    match var {
+                        NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                            path,
+                            lit: Lit::Str(val),
+                            ..
+                        })) if path.get_ident().is_some() => {
+                            vars.insert(path.get_ident().unwrap().to_string(), val.value());
+                        }
+                        _ => panic!(
+                            "Template variables need to be defined as vars(name = \"value\")"
+                        ),
+                    }
+                }
+            }
+            _ => panic!("Unsupported #[hugsqlx(...)] attribute, expected vars(...)"),
+        }
+    }
+    vars
+}
+
+/// Find template variables file in the local crate's CARGO_MANIFEST_DIR or the workspace root.
+fn find_vars_file() -> Option<PathBuf> {
+    let cargo_dir = env::var("CARGO_MANIFEST_DIR").expect("Could not locate Cargo.toml");
+    let candidate_path = Path::new(&cargo_dir).join(template::VARS_FILE);
+    if candidate_path.exists() {
+        return Some(candidate_path);
+    }
+    let candidate_path = workspace_dir().join(template::VARS_FILE);
+    candidate_path.exists().then_some(candidate_path)
+}
+
+/// Locates workspace root with `cargo locate-project`. Spawning cargo is costly, so the path
+/// is located once and shared by all the derives expanded by this process.
 fn workspace_dir() -> PathBuf {
+    static WORKSPACE_DIR: OnceLock<PathBuf> = OnceLock::new();
+    WORKSPACE_DIR.get_or_init(locate_workspace_dir).clone()
+}
+
+fn locate_workspace_dir() -> PathBuf {
     let output = std::process::Command::new(env!("CARGO"))
         .arg("locate-project")
         .arg("--workspace")
@@ -137,11 +257,19 @@ pub fn impl_hug_sqlx(ast: &syn::DeriveInput, ctx: Context) -> TokenStream2 {
     let canonical_path = find_queries_path(queries_paths.remove(0));
 
     let files = if canonical_path.is_dir() {
-        walkdir::WalkDir::new(canonical_path)
+        walkdir::WalkDir::new(&canonical_path)
             .follow_links(true)
             .sort_by_file_name()
             .into_iter()
-            .filter_map(|e| e.ok())
+            .map(|e| {
+                e.unwrap_or_else(|err| {
+                    panic!(
+                        "Could not read queries of {}: {}",
+                        canonical_path.display(),
+                        err
+                    )
+                })
+            })
             .filter(|e| e.file_type().is_file())
             .map(move |e| std::fs::canonicalize(e.path()).expect("Could not get canonical path"))
             .collect()
@@ -154,20 +282,41 @@ pub fn impl_hug_sqlx(ast: &syn::DeriveInput, ctx: Context) -> TokenStream2 {
     let mut functions = TokenStream2::new();
     let mut enums = TokenStream2::new();
 
+    let vars_file = find_vars_file();
+    let vars = template::Vars::new(find_template_vars(ast), vars_file.as_deref());
+    let mut definitions = Vec::new();
     for f in files {
-        if let Ok(input) = fs::read_to_string(f) {
-            match parser::parse_queries(input) {
-                Ok(ast) => {
-                    generate_impl_fns(ast, &ctx, &mut functions, &mut enums);
-                }
-                Err(parse_errs) => parse_errs
-                    .into_iter()
-                    .for_each(|e| eprintln!("Parse error: {}", e)),
-            }
+        let input = fs::read_to_string(&f)
+            .unwrap_or_else(|err| panic!("Could not read queries of {}: {}", f.display(), err));
+        let input = vars.substitute(&input);
+        match parser::parse_queries(input.clone()) {
+            Ok(ast) => definitions.extend(ast),
+            Err(parse_errs) => panic!(
+                "Could not parse queries of {}: {}",
+                f.display(),
+                parse_errs
+                    .iter()
+                    .map(|e| {
+                        let line = input.chars().take(e.span().start).filter(|c| *c == '\n');
+                        format!("{} (line {})", e, line.count() + 1)
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
         }
     }
+    let queries = parser::expand_snippets(definitions);
+    generate_impl_fns(queries, &ctx, &mut functions, &mut enums);
 
+    // Vars file and environment variables are not tracked by cargo, unless referred to
+    // by the generated code
+    let vars_file = vars_file
+        .into_iter()
+        .map(|path| path.to_string_lossy().into_owned());
+    let env_vars = vars.env_vars();
     output_ts.extend(quote! {
+        #(const _: &[u8] = include_bytes!(#vars_file);)*
+        #(const _: Option<&str> = option_env!(#env_vars);)*
         #enums
 
         pub trait HugSql {
@@ -179,319 +328,2255 @@ pub fn impl_hug_sqlx(ast: &syn::DeriveInput, ctx: Context) -> TokenStream2 {
     output_ts
 }
 
-fn generate_cond_block_resolver_fn(query: &Query) -> (TokenStream2, TokenStream2, TokenStream2) {
-    let sql_blocks = &query.sql;
-    let cond_blocks = sql_blocks
-        .iter()
-        .filter(|b| matches!(b, condblock::SqlBlock::Conditional(_, _)))
-        .count();
+/// SQL of a generated function, either known upfront or assembled at runtime.
+enum Sql {
+    /// Expression of `&str` type
+    Static(TokenStream2),
+    /// Expression of `Result<(String, Args), sqlx::Error>` type, assembling SQL string
+    /// and binding additional parameters (if any) to query params.
+    Assembled(TokenStream2),
+}
 
-    if cond_blocks > 0 {
-        let enumeration = Ident::new(&snake_to_pascal(&query.name), Span::call_site());
-        let mut variants = Vec::with_capacity(cond_blocks);
+/// Pieces of code resolving query's SQL, shared by all kinds of generated functions.
+struct SqlResolver {
+    /// Additional function arguments (block resolver, loop collections)
+    args: TokenStream2,
+    sql: Sql,
+    /// Enums passed to block resolver
+    enums: TokenStream2,
+    /// Tuple list splitting query into chunks, along with the tuple arity
+    chunks: Option<(Ident, usize)>,
+}
 
-        // Generate compile-time code that builds the SQL string at runtime
-        let block_processing: Vec<_> = sql_blocks
-            .iter()
-            .map(|block| match block {
-                condblock::SqlBlock::Conditional(id, sql) => {
-                    let variant = Ident::new(&snake_to_pascal(id), Span::call_site());
-                    let quot = quote! {
-                        if block_resolver(#enumeration::#variant) {
-                            result.push('\n');
-                            result.push_str(#sql);
+impl SqlResolver {
+    /// Generates function body calling `query` with resolved SQL and `params`.
+    fn call(&self, query: TokenStream2) -> TokenStream2 {
+        match &self.sql {
+            Sql::Static(sql) => quote! {
+                let sql = #sql;
+                #query
+            },
+            Sql::Assembled(assembly) => quote! {
+                let (sql, params) = #assembly?;
+                let sql = sql.as_str();
+                #query
+            },
+        }
+    }
+
+    /// Generates bound of function's executor, which needs to acquire a connection to run
+    /// query split into chunks.
+    fn executor(&self, db: &Type) -> TokenStream2 {
+        match self.chunks {
+            Some(_) => quote! { sqlx::Acquire<'c, Database = #db> + Send },
+            None => quote! { sqlx::Executor<'c, Database = #db> },
+        }
+    }
+
+    /// Generates function body calling `query` which returns all the results at once.
+    /// Query with a tuple list is run within a transaction, once per chunk of tuples fitting
+    /// `limit` of bound parameters (along with all the other parameters, which are counted
+    /// by assembling SQL with no tuples), with results of all the chunks extending `empty`
+    /// one. Query returning `single` result is extended by one result per chunk.
+    fn call_chunked(
+        &self,
+        query: TokenStream2,
+        empty: TokenStream2,
+        single: bool,
+        limit: usize,
+    ) -> TokenStream2 {
+        let (Some((collection, arity)), Sql::Assembled(assembly)) = (&self.chunks, &self.sql)
+        else {
+            return self.call(query);
+        };
+        let results = if single {
+            quote! { std::iter::once(#query?) }
+        } else {
+            quote! { #query? }
+        };
+        quote! {
+            use sqlx::{Acquire, Arguments};
+            let tuples = #collection;
+            let bound = {
+                let #collection = &tuples[..0];
+                let params = params.clone();
+                let (_, params) = #assembly?;
+                params.len()
+            };
+            let chunk_len = hugsqlx::runtime::chunk_len(#limit, bound, #arity);
+            let mut result = #empty;
+            let mut tx = executor.begin().await?;
+            for #collection in tuples.chunks(chunk_len) {
+                let (sql, params) = {
+                    let params = params.clone();
+                    #assembly?
+                };
+                let sql = sql.as_str();
+                let executor = &mut *tx;
+                result.extend(#results);
+            }
+            tx.commit().await?;
+            Ok(result)
+        }
+    }
+
+    /// Generates function body returning a stream created by `query`. Assembled SQL gets
+    /// moved into the stream to outlive the function call.
+    fn stream(&self, query: TokenStream2) -> TokenStream2 {
+        match &self.sql {
+            Sql::Static(_) => self.call(query),
+            Sql::Assembled(assembly) => quote! {
+                let assembled = #assembly;
+                Box::pin(hugsqlx::runtime::stream! {
+                    match assembled {
+                        Ok((sql, params)) => {
+                            let sql = sql.as_str();
+                            let mut rows = #query;
+                            while let Some(row) = hugsqlx::runtime::next(&mut rows).await {
+                                yield row;
+                            }
                         }
-                    };
-                    variants.push(variant);
-                    quot
-                }
-                condblock::SqlBlock::Literal(sql) => {
-                    quote! {
-                        result.push_str(#sql);
+                        Err(e) => yield Err(e),
                     }
-                }
-            })
-            .collect();
+                })
+            },
+        }
+    }
 
-        // Generate Enums that will be passed to block resolving function
-        let variant_tokens = variants.into_iter().map(|variant| {
-            quote! {
-                #variant,
+    /// Wraps a query returning stream of rows into a stream of `item`s, each one awaited
+    /// upon the `row` before being yielded.
+    fn stream_each(&self, query: TokenStream2, item: TokenStream2) -> TokenStream2 {
+        let produce = quote! {
+            let mut rows = #query;
+            while let Some(row) = hugsqlx::runtime::next(&mut rows).await {
+                yield match row {
+                    Ok(row) => #item,
+                    Err(e) => Err(e),
+                };
             }
-        });
-
-        return (
-            quote! { block_resolver: impl Fn(#enumeration) -> bool + Send, },
-            quote! {
-                &{
-                    let mut result = String::new();
-                    #(#block_processing)*
-                    result
-                }
+        };
+        match &self.sql {
+            Sql::Static(sql) => quote! {
+                let sql = #sql;
+                Box::pin(hugsqlx::runtime::stream! { #produce })
             },
-            quote! {
-                pub enum #enumeration {
-                    #(#variant_tokens)*
-                }
+            Sql::Assembled(assembly) => quote! {
+                let assembled = #assembly;
+                Box::pin(hugsqlx::runtime::stream! {
+                    match assembled {
+                        Ok((sql, params)) => {
+                            let sql = sql.as_str();
+                            #produce
+                        }
+                        Err(e) => yield Err(e),
+                    }
+                })
             },
-        );
+        }
     }
-    let sql = match sql_blocks.first() {
-        Some(condblock::SqlBlock::Literal(sql))
-        | Some(condblock::SqlBlock::Conditional(_, sql)) => sql,
-        None => "",
-    };
-    (TokenStream2::new(), quote! { #sql }, TokenStream2::new())
 }
 
-fn generate_impl_fns(
-    queries: Vec<Query>,
-    ctx: &Context,
-    functions_ts: &mut TokenStream2,
-    enums_ts: &mut TokenStream2,
-) {
-    for q in queries {
-        if let Some(doc) = &q.doc {
-            functions_ts.extend(quote! { #[doc = #doc] });
+/// Generates code binding a value to `params` and pushing its placeholder to `sql`.
+fn generate_bind(value: TokenStream2) -> TokenStream2 {
+    quote! {
+        params.add(#value).map_err(sqlx::Error::Encode)?;
+        params
+            .format_placeholder(&mut sql)
+            .map_err(|e| sqlx::Error::Encode(e.into()))?;
+    }
+}
+
+/// State of SQL assembling code generation, collecting identifiers which need to be
+/// declared by generated function.
+struct BlockContext<'a> {
+    db: &'a Type,
+    params: &'a [Param],
+    identifiers: &'a [parser::Identifier],
+    /// Character quoting identifiers
+    quote: char,
+    empty_list: fn(&Ident) -> TokenStream2,
+    enumeration: Ident,
+    /// Enum variants along with condition identifiers they were created from
+    variants: Vec<(Ident, Ident)>,
+    /// Names and declarations of additional function arguments, in order of appearance
+    args: Vec<(String, TokenStream2)>,
+    /// Optional parameters bound more than once, which need to be cloned
+    shared: BTreeSet<String>,
+    /// Configuration predicates of cfg blocks
+    cfgs: Vec<String>,
+    /// Whether blocks get glued by a `--~where` or `--~set` region, which skips empty ones
+    region: bool,
+}
+
+impl BlockContext<'_> {
+    fn declare(&mut self, name: &str, declaration: TokenStream2) {
+        if !self.args.iter().any(|(n, _)| n == name) {
+            self.args.push((name.to_string(), declaration));
         }
-        match q.kind {
-            Kind::Typed => generate_typed_fn(q, ctx, functions_ts, enums_ts),
-            Kind::Untyped => generate_untyped_fn(q, ctx, functions_ts, enums_ts),
-            Kind::Mapped => generate_mapped_fn(q, ctx, functions_ts, enums_ts),
+    }
+
+    /// Returns enum of identifiers allowed with `-- :ident` declaration of given name, if any.
+    fn allowed_identifiers(&self, name: &str) -> Option<Ident> {
+        self.identifiers
+            .iter()
+            .any(|i| i.name == name && !i.allowed.is_empty())
+            .then(|| format_ident!("{}{}", self.enumeration, snake_to_pascal(name)))
+    }
+
+    /// Returns type of tuples in a `Vec<T>` or `&[T]` argument declared with `-- :param`,
+    /// along with the tuple arity.
+    fn declared_tuple(&self, name: &str) -> (Type, usize) {
+        let element = declared_element(self.params, name);
+        match element {
+            Some(Type::Tuple(tuple)) if !tuple.elems.is_empty() => {
+                let arity = tuple.elems.len();
+                (Type::Tuple(tuple), arity)
+            }
+            _ => panic!(
+                "Tuple list :t*:{} requires declaration of tuples type, like: -- :param {} Vec<(i32, String)>",
+                name, name
+            ),
         }
     }
 }
 
-fn generate_typed_fn(
-    q: Query,
-    Context(db, args, row, result): &Context,
-    functions_ts: &mut TokenStream2,
-    enums_ts: &mut TokenStream2,
+/// Returns type of an argument declared with `-- :param`, if any.
+fn declared_type(params: &[Param], name: &str) -> Option<Type> {
+    params.iter().find(|p| p.name == name).map(|p| {
+        parse_str::<Type>(&p.ty)
+            .unwrap_or_else(|_| panic!("Invalid type of :param {}: {}", p.name, p.ty))
+    })
+}
+
+/// Returns type of elements of a `Vec<T>` or `&[T]` argument declared with `-- :param`,
+/// if any.
+fn declared_element(params: &[Param], name: &str) -> Option<Type> {
+    match declared_type(params, name)? {
+        Type::Path(path) => path
+            .path
+            .segments
+            .into_iter()
+            .last()
+            .filter(|segment| segment.ident == "Vec")
+            .and_then(|segment| match segment.arguments {
+                syn::PathArguments::AngleBracketed(args) => args.args.into_iter().next(),
+                _ => None,
+            })
+            .and_then(|arg| match arg {
+                syn::GenericArgument::Type(ty) => Some(ty),
+                _ => None,
+            }),
+        Type::Reference(reference) => match *reference.elem {
+            Type::Slice(slice) => Some(*slice.elem),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Collects names of optional parameters bound more than once across given blocks.
+fn shared_optionals(
+    blocks: &[condblock::SqlBlock],
+    seen: &mut Vec<String>,
+    shared: &mut BTreeSet<String>,
 ) {
-    let name = Ident::new(&q.name, Span::call_site());
-    let (block_resolver, sql, enums) = generate_cond_block_resolver_fn(&q);
+    for block in blocks {
+        match block {
+            condblock::SqlBlock::Optional(param, sql) => {
+                let binds = condblock::split_binds(sql, &[param])
+                    .into_iter()
+                    .filter(|f| matches!(f, condblock::Fragment::Bind(_)))
+                    .count();
+                if binds > 1 || seen.contains(param) {
+                    shared.insert(param.clone());
+                }
+                seen.push(param.clone());
+            }
+            condblock::SqlBlock::Where(blocks)
+            | condblock::SqlBlock::Set(blocks)
+            | condblock::SqlBlock::Cfg(_, blocks) => shared_optionals(blocks, seen, shared),
+            _ => {}
+        }
+    }
+}
 
-    enums_ts.extend(enums);
+fn generate_block(block: &condblock::SqlBlock, block_ctx: &mut BlockContext) -> TokenStream2 {
+    match block {
+        condblock::SqlBlock::Conditional(id, sql) => {
+            let sql = generate_expansions(sql, block_ctx);
+            let enumeration = &block_ctx.enumeration;
+            let variant = Ident::new(&snake_to_pascal(id), Span::call_site());
+            let quot = quote! {
+                if block_resolver.resolve(#enumeration::#variant) {
+                    sql.push('\n');
+                    #sql
+                }
+            };
+            if !block_ctx.variants.iter().any(|(v, _)| *v == variant) {
+                let field = Ident::new(id, Span::call_site());
+                block_ctx.variants.push((variant, field));
+            }
+            quot
+        }
+        condblock::SqlBlock::Optional(param, sql) => {
+            let ident = Ident::new(param, Span::call_site());
+            let shared = block_ctx.shared.contains(param);
+            let db = block_ctx.db;
+            let declaration = match declared_type(block_ctx.params, param) {
+                Some(ty) => quote! { #ident: #ty, },
+                None if shared => quote! {
+                    #ident: Option<impl sqlx::Encode<'q, #db> + sqlx::Type<#db> + Clone + Send + 'q>,
+                },
+                None => quote! {
+                    #ident: Option<impl sqlx::Encode<'q, #db> + sqlx::Type<#db> + Send + 'q>,
+                },
+            };
+            block_ctx.declare(param, declaration);
 
-    functions_ts.extend(match q.method {
-        Method::FetchMany => {
+            let (value, bind) = if shared {
+                (quote! { &#ident }, generate_bind(quote! { #ident.clone() }))
+            } else {
+                (quote! { #ident }, generate_bind(quote! { #ident }))
+            };
+            let fragments = generate_fragments(sql, param, bind, block_ctx);
             quote! {
-                async fn #name<'q, 'e, 'c, E, T> (executor: E, #block_resolver params: #args) -> futures_core::stream::BoxStream<'e, Result<T, sqlx::Error>>
-                where
-                      'q: 'e,
-                      'c: 'e,
-                      E: sqlx::Executor<'c, Database = #db> + 'e,
-                      T: Send + Unpin + for<'r> sqlx::FromRow<'r, #row> + 'e {
-                    sqlx::query_as_with(#sql, params).fetch(executor)
+                if let Some(#ident) = #value {
+                    sql.push('\n');
+                    #fragments
                 }
             }
-        },
-        Method::FetchOne => {
+        }
+        condblock::SqlBlock::Loop {
+            var,
+            collection,
+            body,
+            separator,
+        } => {
+            // Each repetition would bind all the tuples again, which makes chunking impossible
+            if let Some(condblock::Expansion::TupleList(name)) = condblock::split_expansions(body)
+                .into_iter()
+                .find(|e| matches!(e, condblock::Expansion::TupleList(_)))
+            {
+                panic!(
+                    "Tuple list :t*:{} can't be repeated by --~for {} in {} block",
+                    name, var, collection
+                );
+            }
+            // Repetitions outside of a region leave nothing in place of an empty collection,
+            // which would most likely break the query (like a dangling WHERE)
+            let empty = (!block_ctx.region).then(|| {
+                let message = format!(
+                    "Empty collection of --~for {} in {} block outside of --~where or --~set region",
+                    var, collection
+                );
+                let ident = Ident::new(collection, Span::call_site());
+                quote! {
+                    if #ident.is_empty() {
+                        return Err(sqlx::Error::Protocol(#message.into()));
+                    }
+                }
+            });
+            let collection = Ident::new(collection, Span::call_site());
+            let separator = format!("\n{separator}\n");
+            let db = block_ctx.db;
+            block_ctx.declare(
+                &collection.to_string(),
+                quote! {
+                    #collection: &'q [impl sqlx::Encode<'q, #db> + sqlx::Type<#db> + Sync],
+                },
+            );
+
+            let fragments =
+                generate_fragments(body, var, generate_bind(quote! { item }), block_ctx);
             quote! {
-                async fn #name<'q, 'e, 'c, E, T> (executor: E, #block_resolver params: #args) -> Result<T, sqlx::Error>
-                where
-                      'q: 'e,
-                      'c: 'e,
-                      E: sqlx::Executor<'c, Database = #db> + 'e,
-                      T: Send + Unpin + for<'r> sqlx::FromRow<'r, #row> + 'e {
-                    sqlx::query_as_with(#sql, params).fetch_one(executor).await
+                #empty
+                for (i, item) in #collection.iter().enumerate() {
+                    sql.push_str(if i == 0 { "\n" } else { #separator });
+                    #fragments
                 }
             }
-        },
-        Method::FetchOptional => {
-            quote! {
-                async fn #name<'q, 'e, 'c, E, T> (executor: E, #block_resolver params: #args) -> Result<Option<T>, sqlx::Error>
-                where
-                      'q: 'e,
-                      'c: 'e,
-                      E: sqlx::Executor<'c, Database = #db> + 'e,
-                      T: Send + Unpin + for<'r> sqlx::FromRow<'r, #row> + 'e {
-                    sqlx::query_as_with(#sql, params).fetch_optional(executor).await
+        }
+        condblock::SqlBlock::Where(blocks) | condblock::SqlBlock::Set(blocks) => {
+            let clause = match block {
+                condblock::SqlBlock::Where(_) => quote! {
+                    hugsqlx::runtime::Clause::Where.push(&mut sql, &parts);
+                },
+                // Update with no columns to set isn't valid SQL
+                _ => quote! {
+                    if !hugsqlx::runtime::Clause::Set.push(&mut sql, &parts) {
+                        return Err(sqlx::Error::Protocol("No columns to update".into()));
+                    }
+                },
+            };
+            // Each block is assembled separately, so the empty ones can be skipped
+            let region = std::mem::replace(&mut block_ctx.region, true);
+            let parts = blocks.iter().map(|block| {
+                let block = generate_block(block, block_ctx);
+                quote! {
+                    {
+                        let mut sql = String::new();
+                        #block
+                        parts.push(sql);
+                    }
                 }
+            });
+            let parts: Vec<_> = parts.collect();
+            block_ctx.region = region;
+            quote! {
+                let mut parts = Vec::new();
+                #(#parts)*
+                #clause
+            }
+        }
+        condblock::SqlBlock::Cfg(predicate, blocks) => {
+            let cfg = parse_cfg(predicate);
+            let blocks: Vec<_> = blocks
+                .iter()
+                .map(|block| generate_block(block, block_ctx))
+                .collect();
+            if !block_ctx.cfgs.contains(predicate) {
+                block_ctx.cfgs.push(predicate.clone());
             }
-        },
-        Method::FetchAll => {
             quote! {
-                async fn #name<'q, 'e, 'c, E, T> (executor: E, #block_resolver params: #args) -> Result<Vec<T>, sqlx::Error>
-                where
-                     'q: 'e,
-                     'c: 'e,
-                      E: sqlx::Executor<'c, Database = #db> + 'e,
-                      T: Send + Unpin + for<'r> sqlx::FromRow<'r, #row> + 'e {
-                    sqlx::query_as_with(#sql, params).fetch_all(executor).await
+                if cfg!(#cfg) {
+                    #(#blocks)*
                 }
             }
-        },
-        Method::Execute => {
+        }
+        condblock::SqlBlock::Literal(sql) => {
+            let sql = generate_expansions(sql, block_ctx);
             quote! {
-                async fn #name<'q, 'e, 'c, E> (executor: E, #block_resolver params: #args) -> Result<#result, sqlx::Error>
-                where
-                 'q: 'e,
-                 'c: 'e,
-                 E: sqlx::Executor<'c, Database = #db> + 'e {
-                    sqlx::query_with(#sql, params).execute(executor).await
-                }
+                sql.push('\n');
+                #sql
             }
-        },
-    });
+        }
+        condblock::SqlBlock::Include(name) => {
+            unreachable!("Snippet {} should have been expanded by parser", name)
+        }
+        condblock::SqlBlock::OrderBy { name, .. } => {
+            let ident = Ident::new(name, Span::call_site());
+            let sort = format_ident!("{}{}", block_ctx.enumeration, snake_to_pascal(name));
+            block_ctx.declare(name, quote! { #ident: (#sort, hugsqlx::SortDirection), });
+            quote! {
+                let (column, direction) = #ident;
+                sql.push_str("\nORDER BY ");
+                sql.push_str(column.as_quoted());
+                sql.push(' ');
+                sql.push_str(direction.as_sql());
+            }
+        }
+    }
 }
 
-fn generate_untyped_fn(
-    q: Query,
-    Context(db, args, row, result): &Context,
-    functions_ts: &mut TokenStream2,
-    enums_ts: &mut TokenStream2,
-) {
-    let name = Ident::new(&q.name, Span::call_site());
-    let (block_resolver, sql, enums) = generate_cond_block_resolver_fn(&q);
+/// Generates code pushing SQL fragment to `sql`, with `:v*:name` value lists expanded
+/// into as many placeholders as there are elements in `name` collection, `:t*:name` tuple
+/// lists into parenthesized placeholders of each tuple, and `:i:name` markers as well as
+/// `{{:name}}` slots replaced by quoted identifiers.
+fn generate_expansions(sql: &str, block_ctx: &mut BlockContext) -> TokenStream2 {
+    let expansions: Vec<_> = condblock::split_expansions(sql)
+        .into_iter()
+        .map(|expansion| generate_expansion(expansion, block_ctx))
+        .collect();
+    quote! { #(#expansions)* }
+}
 
-    enums_ts.extend(enums);
+/// Generates code pushing SQL fragment of an optional or loop block to `sql`, with markers
+/// expanded the same way as in any other fragment, and `:name` markers of the block's
+/// parameter replaced by `bind`.
+fn generate_fragments(
+    sql: &str,
+    name: &str,
+    bind: TokenStream2,
+    block_ctx: &mut BlockContext,
+) -> TokenStream2 {
+    let fragments: Vec<_> = condblock::split_expansions(sql)
+        .into_iter()
+        .flat_map(|expansion| match expansion {
+            condblock::Expansion::Sql(sql) => condblock::split_binds(&sql, &[name])
+                .into_iter()
+                .map(|fragment| match fragment {
+                    condblock::Fragment::Sql(sql) => quote! { sql.push_str(#sql); },
+                    condblock::Fragment::Bind(_) => bind.clone(),
+                })
+                .collect(),
+            expansion => vec![generate_expansion(expansion, block_ctx)],
+        })
+        .collect();
+    quote! { #(#fragments)* }
+}
 
-    functions_ts.extend(match q.method {
-        Method::FetchMany => {
+fn generate_expansion(
+    expansion: condblock::Expansion,
+    block_ctx: &mut BlockContext,
+) -> TokenStream2 {
+    let db = block_ctx.db;
+    match expansion {
+        condblock::Expansion::Sql(sql) => quote! { sql.push_str(#sql); },
+        condblock::Expansion::ValueList(collection) => {
+            let ident = Ident::new(&collection, Span::call_site());
+            let bind = generate_bind(quote! { item });
+            block_ctx.declare(
+                &collection,
+                quote! {
+                    #ident: &'q [impl sqlx::Encode<'q, #db> + sqlx::Type<#db> + Sync],
+                },
+            );
+            // Empty list makes no valid SQL, an empty subquery works for both IN
+            // and NOT IN instead
+            let empty_list = (block_ctx.empty_list)(&ident);
             quote! {
-                async fn #name<'q, 'e, 'c, E> (executor: E, #block_resolver params: #args) -> futures_core::stream::BoxStream<'e, Result<#row, sqlx::Error>>
-                where
-                 'q: 'e,
-                 'c: 'e,
-                 E: sqlx::Executor<'c, Database = #db> + 'e {
-                    sqlx::query_with(#sql, params).fetch(executor)
+                if #ident.is_empty() {
+                    #empty_list
+                }
+                for (i, item) in #ident.iter().enumerate() {
+                    if i > 0 {
+                        sql.push_str(", ");
+                    }
+                    #bind
                 }
             }
-        },
-        Method::FetchOne => {
+        }
+        condblock::Expansion::TupleList(collection) => {
+            let ident = Ident::new(&collection, Span::call_site());
+            let (ty, arity) = block_ctx.declared_tuple(&collection);
+            let binds = (0..arity).map(|i| {
+                let index = syn::Index::from(i);
+                let bind = generate_bind(quote! { &item.#index });
+                match i {
+                    0 => bind,
+                    _ => quote! { sql.push_str(", "); #bind },
+                }
+            });
+            block_ctx.declare(&collection, quote! { #ident: &'q [#ty], });
             quote! {
-                async fn #name<'q, 'e, 'c, E> (executor: E, #block_resolver params: #args) -> Result<#row, sqlx::Error>
-                where
-                 'q: 'e,
-                 'c: 'e,
-                 E: sqlx::Executor<'c, Database = #db> + 'e {
-                    sqlx::query_with(#sql, params).fetch_one(executor).await
+                for (i, item) in #ident.iter().enumerate() {
+                    sql.push_str(if i > 0 { ", (" } else { "(" });
+                    #(#binds)*
+                    sql.push(')');
                 }
             }
-        },
-        Method::FetchOptional => {
-            quote! {
-                async fn #name<'q, 'e, 'c, E> (executor: E, #block_resolver params: #args) -> Result<Option<#row>, sqlx::Error>
-                where
-                 'q: 'e,
-                 'c: 'e,
-                 E: sqlx::Executor<'c, Database = #db> + 'e {
-                    sqlx::query_with(#sql, params).fetch_optional(executor).await
+        }
+        condblock::Expansion::Identifier(name) => {
+            let ident = Ident::new(&name, Span::call_site());
+            let quote = block_ctx.quote;
+            match block_ctx.allowed_identifiers(&name) {
+                Some(enumeration) => {
+                    block_ctx.declare(&name, quote! { #ident: #enumeration, });
+                    quote! { sql.push_str(#ident.as_quoted()); }
+                }
+                None => {
+                    block_ctx.declare(&name, quote! { #ident: &str, });
+                    quote! {
+                        sql.push_str(
+                            &hugsqlx::runtime::quote_identifier(#name, #ident, #quote)
+                                .map_err(|e| sqlx::Error::Encode(e.into()))?,
+                        );
+                    }
                 }
             }
-        },
-        Method::FetchAll => {
+        }
+        condblock::Expansion::Slot(name) => {
+            let quote = block_ctx.quote;
             quote! {
-                async fn #name<'q, 'e, 'c, E> (executor: E, #block_resolver params: #args) -> Result<Vec<#row>, sqlx::Error>
-                where
-                 'q: 'e,
-                 'c: 'e,
-                 E: sqlx::Executor<'c, Database = #db> + 'e {
-                    sqlx::query_with(#sql, params).fetch_all(executor).await
-                }
+                sql.push_str(
+                    &idents
+                        .quoted(#name, #quote)
+                        .map_err(|e| sqlx::Error::Encode(e.into()))?,
+                );
             }
-        },
-        Method::Execute => {
+        }
+    }
+}
+
+/// Generates enums of identifiers restricted to allowed ones with `-- :ident` declaration.
+fn generate_identifier_enums(query: &Query, enumeration: &Ident, quote: char) -> TokenStream2 {
+    let cfg_attr = generate_cfg_attr(query);
+    query
+        .identifiers
+        .iter()
+        .filter(|identifier| !identifier.allowed.is_empty())
+        .map(|identifier| {
+            let name = format_ident!("{}{}", enumeration, snake_to_pascal(&identifier.name));
+            let (variants, quoted): (Vec<_>, Vec<_>) = identifier
+                .allowed
+                .iter()
+                .map(|allowed| {
+                    (
+                        Ident::new(&snake_to_pascal(allowed), Span::call_site()),
+                        format!("{quote}{allowed}{quote}"),
+                    )
+                })
+                .unzip();
+            let doc = format!(
+                "Identifiers allowed in place of `:i:{}` marker of `{}` query.",
+                identifier.name, query.name
+            );
             quote! {
-                async fn #name<'q, 'e, 'c, E> (executor: E, #block_resolver params: #args) -> Result<#result, sqlx::Error>
-                where
-                 'q: 'e,
-                 'c: 'e,
-                 E: sqlx::Executor<'c, Database = #db> + 'e {
-                    sqlx::query_with(#sql, params).execute(executor).await
+                #cfg_attr
+                #[doc = #doc]
+                #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+                pub enum #name {
+                    #(#variants,)*
+                }
+
+                #cfg_attr
+                impl #name {
+                    /// Returns identifier quoted the way database expects.
+                    pub fn as_quoted(&self) -> &'static str {
+                        match self {
+                            #(#name::#variants => #quoted,)*
+                        }
+                    }
                 }
             }
-        },
-    });
+        })
+        .collect()
 }
 
-fn generate_mapped_fn(
-    q: Query,
-    Context(db, args, row, result): &Context,
-    functions_ts: &mut TokenStream2,
-    enums_ts: &mut TokenStream2,
-) {
-    let name = Ident::new(&q.name, Span::call_site());
-    let (block_resolver, sql, enums) = generate_cond_block_resolver_fn(&q);
-
-    enums_ts.extend(enums);
+/// Generates enums of columns allowed by `--~order_by` blocks, defaulting to declared one.
+fn generate_sort_enums(query: &Query, enumeration: &Ident, quote: char) -> TokenStream2 {
+    let cfg_attr = generate_cfg_attr(query);
+    condblock::order_bys(&query.sql)
+        .into_iter()
+        .map(|block| {
+            let condblock::SqlBlock::OrderBy {
+                name,
+                columns,
+                default,
+            } = block
+            else {
+                unreachable!()
+            };
+            let sort = format_ident!("{}{}", enumeration, snake_to_pascal(name));
+            let (idents, quoted): (Vec<_>, Vec<_>) = columns
+                .iter()
+                .map(|column| {
+                    (
+                        Ident::new(&snake_to_pascal(column), Span::call_site()),
+                        format!("{quote}{column}{quote}"),
+                    )
+                })
+                .unzip();
+            let variants = idents.iter().zip(columns).map(|(ident, column)| {
+                let default = (column == default).then(|| quote! { #[default] });
+                quote! { #default #ident }
+            });
+            let doc = format!(
+                "Columns `{}` query might be ordered by with `{}` argument.",
+                query.name, name
+            );
+            quote! {
+                #cfg_attr
+                #[doc = #doc]
+                #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+                pub enum #sort {
+                    #(#variants,)*
+                }
+
+                #cfg_attr
+                impl #sort {
+                    /// Returns column quoted the way database expects.
+                    pub fn as_quoted(&self) -> &'static str {
+                        match self {
+                            #(#sort::#idents => #quoted,)*
+                        }
+                    }
+                }
+            }
+        })
+        .collect()
+}
+
+fn parse_cfg(predicate: &str) -> TokenStream2 {
+    predicate
+        .parse()
+        .unwrap_or_else(|_| panic!("Invalid configuration predicate: {}", predicate))
+}
+
+/// Renders SQL of all the combinations of conditional blocks (identified by `fields`),
+/// one table per combination of `cfgs` predicates. Bits of a table index stand for
+/// satisfied predicates, bits of an index within a table for included blocks, both in
+/// order of appearance. Returns `None` if there are too many blocks or predicates, or
+/// if SQL depends on runtime values.
+fn precompute_sql(
+    blocks: &[condblock::SqlBlock],
+    fields: &[Ident],
+    cfgs: &[String],
+) -> Option<Vec<Vec<String>>> {
+    if fields.len() > MAX_PRECOMPUTED_BLOCKS || cfgs.len() > MAX_PRECOMPUTED_CFGS {
+        return None;
+    }
+    (0..1usize << cfgs.len())
+        .map(|cfg_mask| {
+            (0..1usize << fields.len())
+                .map(|mask| {
+                    condblock::render_sql(blocks, &|condition| match condition {
+                        condblock::Condition::Block(id) => fields
+                            .iter()
+                            .position(|field| *field == id)
+                            .is_some_and(|bit| mask & (1 << bit) != 0),
+                        condblock::Condition::Cfg(predicate) => cfgs
+                            .iter()
+                            .position(|cfg| cfg == predicate)
+                            .is_some_and(|bit| cfg_mask & (1 << bit) != 0),
+                    })
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Generates `#[cfg(...)]` attribute for items of a query with `:cfg` predicate.
+fn generate_cfg_attr(query: &Query) -> TokenStream2 {
+    match &query.cfg {
+        Some(predicate) => {
+            let cfg = parse_cfg(predicate);
+            quote! { #[cfg(#cfg)] }
+        }
+        None => TokenStream2::new(),
+    }
+}
+
+fn generate_cond_block_resolver_fn(query: &Query, ctx: &Context) -> SqlResolver {
+    let Context {
+        db,
+        args,
+        quote,
+        positional,
+        empty_list,
+        ..
+    } = ctx;
+    let quote = *quote;
+    let sql_blocks = &query.sql;
+    let runtime_blocks = sql_blocks
+        .iter()
+        .filter(
+            |b| !matches!(b, condblock::SqlBlock::Literal(sql) if !condblock::has_expansions(sql)),
+        )
+        .count();
+
+    if runtime_blocks > 0 {
+        let enumeration = Ident::new(&snake_to_pascal(&query.name), Span::call_site());
+        let mut tuple_lists = condblock::tuple_lists(sql_blocks);
+        tuple_lists.dedup();
+        if tuple_lists.len() > 1 {
+            panic!(
+                "Query {} may contain a single tuple list only, found: {}",
+                query.name,
+                tuple_lists.join(", ")
+            );
+        }
+        if *positional && condblock::has_placeholders_after_binds(sql_blocks) {
+            panic!(
+                "Query {} has ? placeholder following a parameter bound at runtime. Such parameters are bound after the ones passed with params!, so need to come after all ? placeholders",
+                query.name
+            );
+        }
+        let mut shared = BTreeSet::new();
+        let mut seen = Vec::new();
+        shared_optionals(sql_blocks, &mut seen, &mut shared);
+        // SQL of each chunk (or parameter set of a batch) gets assembled anew, binding
+        // optional parameters again
+        if !tuple_lists.is_empty() || query.method == Method::Batch {
+            shared.extend(seen);
+        }
+
+        let mut block_ctx = BlockContext {
+            db,
+            params: &query.params,
+            identifiers: &query.identifiers,
+            quote,
+            empty_list: *empty_list,
+            enumeration,
+            variants: Vec::new(),
+            args: Vec::new(),
+            shared,
+            cfgs: Vec::new(),
+            region: false,
+        };
+
+        // Generate compile-time code that builds the SQL string at runtime
+        let block_processing: Vec<_> = sql_blocks
+            .iter()
+            .map(|block| generate_block(block, &mut block_ctx))
+            .collect();
+        let chunks = tuple_lists.first().map(|collection| {
+            let (_, arity) = block_ctx.declared_tuple(collection);
+            (Ident::new(collection, Span::call_site()), arity)
+        });
+        let BlockContext {
+            enumeration,
+            variants,
+            args: block_args,
+            cfgs,
+            ..
+        } = block_ctx;
+        let cfg_attr = generate_cfg_attr(query);
+
+        let (variants, fields): (Vec<_>, Vec<_>) = variants.into_iter().unzip();
+        let mut resolver_args = TokenStream2::new();
+        let mut enums = generate_identifier_enums(query, &enumeration, quote);
+        enums.extend(generate_sort_enums(query, &enumeration, quote));
+
+        if !variants.is_empty() {
+            let flags = format_ident!("{}Flags", enumeration);
+            let flags_doc = format!(
+                "Flags including conditional blocks of `{}` query. An alternative to block resolving function.",
+                query.name
+            );
+
+            // Generate Enums that will be passed to block resolving function, and a struct
+            // resolving blocks by its boolean fields
+            resolver_args.extend(quote! {
+                block_resolver: impl hugsqlx::BlockResolver<#enumeration> + Send,
+            });
+            enums.extend(quote! {
+                #cfg_attr
+                #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+                pub enum #enumeration {
+                    #(#variants,)*
+                }
+
+                #cfg_attr
+                #[doc = #flags_doc]
+                #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+                pub struct #flags {
+                    #(pub #fields: bool,)*
+                }
+
+                #cfg_attr
+                impl #flags {
+                    #(
+                        pub fn #fields(mut self, included: bool) -> Self {
+                            self.#fields = included;
+                            self
+                        }
+                    )*
+                }
+
+                #cfg_attr
+                impl hugsqlx::BlockResolver<#enumeration> for #flags {
+                    fn resolve(&self, block: #enumeration) -> bool {
+                        match block {
+                            #(#enumeration::#variants => self.#fields,)*
+                        }
+                    }
+                }
+            });
+        }
+        resolver_args.extend(block_args.into_iter().map(|(_, declaration)| declaration));
+
+        // With a few conditional blocks only, all the SQL combinations get precomputed.
+        // Resolved blocks make a bitmask pointing the right one, within a table of
+        // combinations compiled in for the cfg predicates satisfied by the build.
+        let sql = match precompute_sql(sql_blocks, &fields, &cfgs) {
+            Some(tables) if variants.is_empty() && cfgs.is_empty() => {
+                let sql = &tables[0][0];
+                Sql::Static(quote! { #sql })
+            }
+            Some(tables) => {
+                let gates = (0..tables.len()).map(|mask| {
+                    let (on, off): (Vec<_>, Vec<_>) = cfgs
+                        .iter()
+                        .enumerate()
+                        .map(|(bit, predicate)| (bit, parse_cfg(predicate)))
+                        .partition(|(bit, _)| mask & (1 << bit) != 0);
+                    let on = on.into_iter().map(|(_, cfg)| cfg);
+                    let off = off.into_iter().map(|(_, cfg)| cfg);
+                    quote! { #[cfg(all(#(#on,)* #(not(#off),)*))] }
+                });
+                if variants.is_empty() {
+                    let sqls = tables.into_iter().map(|table| table[0].clone());
+                    Sql::Static(quote! {
+                        {
+                            #(
+                                #gates
+                                const SQL: &str = #sqls;
+                            )*
+                            SQL
+                        }
+                    })
+                } else {
+                    let len = 1usize << variants.len();
+                    let bits = (0..variants.len()).map(|bit| 1usize << bit);
+                    let tables = tables.into_iter().map(|table| quote! { [#(#table),*] });
+                    Sql::Static(quote! {
+                        {
+                            #(
+                                #gates
+                                const SQL: [&str; #len] = #tables;
+                            )*
+                            let mut mask = 0;
+                            #(
+                                if block_resolver.resolve(#enumeration::#variants) {
+                                    mask |= #bits;
+                                }
+                            )*
+                            SQL[mask]
+                        }
+                    })
+                }
+            }
+            None => Sql::Assembled(quote! {
+                (|| -> Result<(String, #args), sqlx::Error> {
+                    use sqlx::Arguments;
+                    let mut params = params;
+                    let mut sql = String::new();
+                    #(#block_processing)*
+                    Ok((sql, params))
+                })()
+            }),
+        };
+
+        return SqlResolver {
+            args: resolver_args,
+            sql,
+            enums,
+            chunks,
+        };
+    }
+    let sql = match sql_blocks.first() {
+        Some(condblock::SqlBlock::Literal(sql)) => sql.as_str(),
+        _ => "",
+    };
+    SqlResolver {
+        args: TokenStream2::new(),
+        sql: Sql::Static(quote! { #sql }),
+        enums: TokenStream2::new(),
+        chunks: None,
+    }
+}
+
+/// Generates code binding a column array per mapped field of bulk query's slice elements.
+/// Returns the slice argument along with type of its elements.
+fn generate_bulk_columns(query: &Query) -> (Ident, Type, TokenStream2) {
+    let Some(Fields { name, fields }) = &query.fields else {
+        panic!(
+            "Bulk query {} requires a field mapping, like: -- :fields rows (user_id, name)",
+            query.name
+        );
+    };
+    let ty = declared_element(&query.params, name).unwrap_or_else(|| {
+        panic!(
+            "Bulk query {} requires declaration of {} elements type, like: -- :param {} &[User]",
+            query.name, name, name
+        )
+    });
+    let rows = Ident::new(name, Span::call_site());
+    let fields = fields
+        .iter()
+        .map(|field| Ident::new(field, Span::call_site()));
+    let binds = quote! {
+        #(
+            params
+                .add(#rows.iter().map(|row| &row.#fields).collect::<Vec<_>>())
+                .map_err(sqlx::Error::Encode)?;
+        )*
+    };
+    (rows, ty, binds)
+}
+
+/// Wraps body of an execute query with `:affects` hint, so that it runs within a transaction
+/// (or a savepoint) rolled back when number of affected rows is not the expected one.
+/// Returns executor bound and error type along with the body.
+fn generate_affects(
+    query: &Query,
+    executor: TokenStream2,
+    body: TokenStream2,
+    db: &Type,
+    result: &Type,
+) -> (TokenStream2, TokenStream2, TokenStream2) {
+    let Some(Affects { min, max }) = &query.affects else {
+        return (executor, body, quote! { sqlx::Error });
+    };
+    let (range, expected) = match max {
+        Some(max) if max == min => (quote! { #min..=#max }, min.to_string()),
+        Some(max) => (quote! { #min..=#max }, format!("{}..={}", min, max)),
+        None => (quote! { #min.. }, format!("{}..", min)),
+    };
+    let name = &query.name;
+    let body = quote! {
+        use sqlx::Acquire;
+        let mut tx = executor.begin().await.map_err(hugsqlx::RowsAffectedError::Query)?;
+        let executor = &mut *tx;
+        let result: Result<#result, sqlx::Error> = async { #body }.await;
+        let result = result.map_err(hugsqlx::RowsAffectedError::Query)?;
+        let rows_affected = result.rows_affected();
+        if !(#range).contains(&rows_affected) {
+            // Dropped transaction gets rolled back
+            return Err(hugsqlx::RowsAffectedError::Unexpected {
+                query: #name,
+                expected: #expected,
+                rows_affected,
+            });
+        }
+        tx.commit().await.map_err(hugsqlx::RowsAffectedError::Query)?;
+        Ok(result)
+    };
+    (
+        quote! { sqlx::Acquire<'c, Database = #db> + Send },
+        body,
+        quote! { hugsqlx::RowsAffectedError<sqlx::Error> },
+    )
+}
+
+/// Turns low-level result of an execute query into portable `hugsqlx::ExecOutcome`, unless
+/// the query asks for raw one. Returns type of function output along with the body.
+fn generate_outcome(
+    query: &Query,
+    body: TokenStream2,
+    error: &TokenStream2,
+    Context {
+        result,
+        last_insert_id,
+        ..
+    }: &Context,
+) -> (TokenStream2, TokenStream2) {
+    if query.method == Method::ExecuteRaw {
+        return (quote! { #result }, body);
+    }
+    // Id of last inserted row is kept per connection, so it's stale unless query inserts
+    let last_insert_id = match condblock::is_insert(&query.sql) {
+        true => last_insert_id.clone(),
+        false => quote! { None },
+    };
+    let body = quote! {
+        let result: Result<#result, #error> = async { #body }.await;
+        result.map(|result| hugsqlx::ExecOutcome {
+            rows_affected: result.rows_affected(),
+            last_insert_id: #last_insert_id,
+        })
+    };
+    (quote! { hugsqlx::ExecOutcome }, body)
+}
+
+/// Batches stream of a query declaring chunk size into vectors of up to that many items.
+/// Returns type of stream items along with the body.
+fn generate_chunks(
+    query: &Query,
+    item: TokenStream2,
+    body: TokenStream2,
+) -> (TokenStream2, TokenStream2) {
+    match query.method {
+        Method::FetchChunks(size) => (
+            quote! { Result<Vec<#item>, sqlx::Error> },
+            quote! { hugsqlx::runtime::chunks({ #body }, #size) },
+        ),
+        _ => (quote! { Result<#item, sqlx::Error> }, body),
+    }
+}
+
+/// Generates function of a query returning the same result no matter query kind: a scalar
+/// value, whether any row was found, or number of affected rows.
+fn generate_kindless_fn(
+    q: &Query,
+    resolver: &SqlResolver,
+    Context {
+        db,
+        args,
+        result,
+        limit,
+        ..
+    }: &Context,
+) -> TokenStream2 {
+    let name = Ident::new(&q.name, Span::call_site());
+    let resolver_args = &resolver.args;
+
+    match q.method {
+        Method::FetchScalar => {
+            let body = resolver.call(quote! {
+                sqlx::query_scalar_with(sql, params).fetch_one(executor).await
+            });
+            quote! {
+                async fn #name<'q, 'e, 'c, E, T> (executor: E, #resolver_args params: #args) -> Result<T, sqlx::Error>
+                where
+                      'q: 'e,
+                      'c: 'e,
+                      E: sqlx::Executor<'c, Database = #db> + 'e,
+                      T: Send + Unpin + for<'r> sqlx::Decode<'r, #db> + sqlx::Type<#db> + 'e {
+                    #body
+                }
+            }
+        }
+        Method::Exists => {
+            let body = resolver.call(quote! {
+                sqlx::query_with(sql, params)
+                    .fetch_optional(executor)
+                    .await
+                    .map(|row| row.is_some())
+            });
+            quote! {
+                async fn #name<'q, 'e, 'c, E> (executor: E, #resolver_args params: #args) -> Result<bool, sqlx::Error>
+                where
+                 'q: 'e,
+                 'c: 'e,
+                 E: sqlx::Executor<'c, Database = #db> + 'e {
+                    #body
+                }
+            }
+        }
+        Method::RowsAffected => {
+            let executor = resolver.executor(db);
+            let body = resolver.call_chunked(
+                quote! { sqlx::query_with(sql, params).execute(executor).await },
+                quote! { #result::default() },
+                true,
+                *limit,
+            );
+            let (executor, body, error) = generate_affects(q, executor, body, db, result);
+            quote! {
+                async fn #name<'q, 'e, 'c, E> (executor: E, #resolver_args params: #args) -> Result<u64, #error>
+                where
+                 'q: 'e,
+                 'c: 'e,
+                 E: #executor + 'e {
+                    let result: Result<#result, #error> = async { #body }.await;
+                    result.map(|result| result.rows_affected())
+                }
+            }
+        }
+        _ => unreachable!("Query {} returns result of its kind", q.name),
+    }
+}
+
+/// Conversion of fetched rows into values of a grouping query, which depends on query kind.
+struct RowValue {
+    /// Type of a value
+    ty: TokenStream2,
+    /// Generic parameters and their bounds required by conversion
+    generics: TokenStream2,
+    bounds: TokenStream2,
+    /// Additional function arguments (like a mapper), along with code preparing them for use
+    args: TokenStream2,
+    prelude: TokenStream2,
+    /// Expression turning a `row` into `Result` of a value
+    value: TokenStream2,
+}
+
+/// Generates function of a query folding all the fetched rows by a key column: either into
+/// a map of values (`:map`), a map of grouped values (`:group`), or into parents with their
+/// children nested (`:nest`, typed queries only).
+fn generate_grouped_fn(
+    q: &Query,
+    resolver: &SqlResolver,
+    Context {
+        db,
+        args,
+        row,
+        limit,
+        ..
+    }: &Context,
+    RowValue {
+        ty,
+        generics,
+        bounds,
+        args: value_args,
+        prelude,
+        value,
+    }: RowValue,
+) -> TokenStream2 {
+    let name = Ident::new(&q.name, Span::call_site());
+    let resolver_args = &resolver.args;
+    let executor = resolver.executor(db);
+    let body = resolver.call_chunked(
+        quote! { sqlx::query_with(sql, params).fetch_all(executor).await },
+        quote! { Vec::new() },
+        false,
+        *limit,
+    );
+    let rows = quote! {
+        let rows: Result<Vec<#row>, sqlx::Error> = async { #body }.await;
+    };
+    let (output, key_bounds, body) = match &q.method {
+        Method::Map(key) => (
+            quote! { std::collections::HashMap<K, #ty> },
+            quote! { Eq + std::hash::Hash },
+            quote! {
+                #rows
+                #prelude
+                let mut result = std::collections::HashMap::new();
+                for row in rows? {
+                    let key = sqlx::Row::try_get(&row, #key)?;
+                    result.insert(key, #value?);
+                }
+                Ok(result)
+            },
+        ),
+        Method::Group(key) => (
+            quote! { std::collections::HashMap<K, Vec<#ty>> },
+            quote! { Eq + std::hash::Hash },
+            quote! {
+                #rows
+                #prelude
+                let mut result = std::collections::HashMap::<K, Vec<_>>::new();
+                for row in rows? {
+                    let key = sqlx::Row::try_get(&row, #key)?;
+                    result.entry(key).or_default().push(#value?);
+                }
+                Ok(result)
+            },
+        ),
+        Method::Nest(key, prefix) => (
+            quote! { Vec<#ty> },
+            quote! { PartialEq },
+            quote! {
+                use sqlx::{Column, Row, ValueRef};
+                #rows
+                let mut result = Vec::new();
+                let mut last = None;
+                for row in rows? {
+                    let key: K = row.try_get(#key)?;
+                    // Consecutive rows of the same key belong to the same parent
+                    if last.as_ref() != Some(&key) {
+                        result.push(#value?);
+                        last = Some(key);
+                    }
+                    // Outer joined child is missing if none of its columns is set
+                    let nested = row.columns().iter().any(|column| {
+                        column.name().starts_with(#prefix)
+                            && row
+                                .try_get_raw(column.ordinal())
+                                .is_ok_and(|value| !value.is_null())
+                    });
+                    if let (true, Some(parent)) = (nested, result.last_mut()) {
+                        let child = hugsqlx::NestChild::from_prefixed_row(&row, #prefix)
+                            .map_err(|e| match e.downcast::<sqlx::Error>() {
+                                Ok(e) => *e,
+                                Err(e) => sqlx::Error::Decode(e),
+                            })?;
+                        hugsqlx::Nest::children(parent).push(child);
+                    }
+                }
+                Ok(result)
+            },
+        ),
+        _ => unreachable!("Query {} doesn't group its results", q.name),
+    };
+    quote! {
+        async fn #name<'q, 'e, 'c, E, K, #generics> (executor: E, #resolver_args params: #args, #value_args) -> Result<#output, sqlx::Error>
+        where
+              'q: 'e,
+              'c: 'e,
+              E: #executor + 'e,
+              K: Send + #key_bounds + for<'r> sqlx::Decode<'r, #db> + sqlx::Type<#db> + 'e,
+              #bounds {
+            #body
+        }
+    }
+}
+
+/// Generates function of a query streaming its rows through a server-side cursor, declared
+/// within a transaction and fetched from in chunks of declared size. Dropped stream rolls
+/// the transaction back, which closes the cursor too.
+fn generate_cursor_fn(
+    q: &Query,
+    resolver: &SqlResolver,
+    Context {
+        db, args, cursors, ..
+    }: &Context,
+    RowValue {
+        ty,
+        generics,
+        bounds,
+        args: value_args,
+        prelude,
+        value,
+    }: RowValue,
+) -> TokenStream2 {
+    let Method::Cursor(size) = q.method else {
+        unreachable!("Query {} doesn't declare a cursor", q.name)
+    };
+    if !cursors {
+        panic!(
+            "Cursor query {} requires a database declaring cursors, like postgres",
+            q.name
+        );
+    }
+    let name = Ident::new(&q.name, Span::call_site());
+    let resolver_args = &resolver.args;
+    let query = &q.name;
+    let assembled = match &resolver.sql {
+        Sql::Static(sql) => quote! { Ok::<_, sqlx::Error>(((#sql).to_string(), params)) },
+        Sql::Assembled(assembly) => assembly.clone(),
+    };
+    quote! {
+        async fn #name<'q, 'e, 'c, E, #generics> (executor: E, #resolver_args params: #args, #value_args) -> futures_core::stream::BoxStream<'e, Result<#ty, sqlx::Error>>
+        where
+              'q: 'e,
+              'c: 'e,
+              E: sqlx::Acquire<'c, Database = #db> + Send + 'e,
+              #bounds {
+            let assembled = #assembled;
+            Box::pin(hugsqlx::runtime::stream! {
+                #prelude
+                let declared = async {
+                    use sqlx::Acquire;
+                    let (sql, params) = assembled?;
+                    // Stream might be opened more than once on the same connection
+                    let cursor = hugsqlx::runtime::cursor_name(#query);
+                    let mut tx = executor.begin().await?;
+                    sqlx::query_with(&format!("DECLARE {} NO SCROLL CURSOR FOR {}", cursor, sql), params)
+                        .execute(&mut *tx)
+                        .await?;
+                    Ok::<_, sqlx::Error>((tx, cursor))
+                }
+                .await;
+                let (mut tx, cursor) = match declared {
+                    Ok(declared) => declared,
+                    Err(e) => {
+                        yield Err(e);
+                        return;
+                    }
+                };
+                let fetch = format!("FETCH {} FROM {}", #size, cursor);
+                loop {
+                    let rows = match sqlx::query(&fetch).fetch_all(&mut *tx).await {
+                        Ok(rows) => rows,
+                        Err(e) => {
+                            yield Err(e);
+                            return;
+                        }
+                    };
+                    let fetched = rows.len();
+                    for row in rows {
+                        yield #value;
+                    }
+                    if fetched < #size {
+                        break;
+                    }
+                }
+                let closed = async {
+                    sqlx::query(&format!("CLOSE {}", cursor)).execute(&mut *tx).await?;
+                    tx.commit().await
+                }
+                .await;
+                if let Err(e) = closed {
+                    yield Err(e);
+                }
+            })
+        }
+    }
+}
+
+/// Generates function of a query returning a page of results. Query gets wrapped into a
+/// keyset predicate on its order keys if it declares any, or gets limited by offset otherwise.
+/// One row more than requested is fetched to find out whether there are more pages.
+fn generate_paged_fn(
+    q: &Query,
+    resolver: &SqlResolver,
+    Context { db, args, .. }: &Context,
+    RowValue {
+        ty,
+        generics,
+        bounds,
+        args: value_args,
+        prelude,
+        value,
+    }: RowValue,
+) -> TokenStream2 {
+    let name = Ident::new(&q.name, Span::call_site());
+    let resolver_args = &resolver.args;
+    let keys = q
+        .order_key
+        .iter()
+        .enumerate()
+        .map(|(i, column)| (format_ident!("K{}", i), column))
+        .collect::<Vec<_>>();
+    let key_types = keys.iter().map(|(key, _)| key).collect::<Vec<_>>();
+    let key_bounds = key_types.iter().map(|key| {
+        quote! {
+            #key: Send + hugsqlx::PageKey + for<'r> sqlx::Decode<'r, #db> + sqlx::Encode<'q, #db> + sqlx::Type<#db> + 'q,
+        }
+    });
+    let (paging, next_cursor) = if keys.is_empty() {
+        (
+            quote! {
+                let offset = match &page.cursor {
+                    Some(cursor) => cursor.offset().ok_or_else(|| invalid(cursor))?,
+                    None => 0,
+                };
+                let sql = hugsqlx::runtime::offset_sql(query, limit, offset);
+            },
+            quote! { hugsqlx::PageCursor::from_offset(offset + page.size as u64) },
+        )
+    } else {
+        let columns = q.order_key.iter().map(|key| {
+            let column = &key.column;
+            match key.descending {
+                true => quote! { (#column, hugsqlx::SortDirection::Desc) },
+                false => quote! { (#column, hugsqlx::SortDirection::Asc) },
+            }
+        });
+        let arity = keys.len();
+        let binds = keys.iter().enumerate().map(|(i, (key, _))| {
+            let bind = generate_bind(quote! { value });
+            quote! {
+                #i => {
+                    let value: #key = hugsqlx::PageKey::from_key(key).ok_or_else(|| invalid(cursor))?;
+                    #bind
+                }
+            }
+        });
+        let decoded = keys.iter().map(|(key, parser::OrderKey { column, .. })| {
+            quote! { hugsqlx::PageKey::to_key(&sqlx::Row::try_get::<#key, _>(row, #column)?) }
+        });
+        (
+            quote! {
+                use sqlx::Arguments;
+                let mut params = params;
+                let keys = match &page.cursor {
+                    Some(cursor) => Some(
+                        cursor
+                            .keys()
+                            .filter(|keys| keys.len() == #arity)
+                            .ok_or_else(|| invalid(cursor))?,
+                    ),
+                    None => None,
+                };
+                let sql = hugsqlx::runtime::keyset_sql(
+                    query,
+                    &[#(#columns),*],
+                    keys.as_deref(),
+                    limit,
+                    |mut sql, i, key| {
+                        let cursor = page.cursor.as_ref().unwrap();
+                        match i {
+                            #(#binds)*
+                            _ => unreachable!(),
+                        }
+                        Ok::<_, sqlx::Error>(())
+                    },
+                )?;
+            },
+            quote! { hugsqlx::PageCursor::from_keys(&[#(#decoded),*]) },
+        )
+    };
+    let body = resolver.call(quote! {
+        use sqlx::Acquire;
+        let invalid = |cursor: &hugsqlx::PageCursor| {
+            sqlx::Error::Decode(format!("Invalid page cursor: {}", cursor).into())
+        };
+        if page.size == 0 {
+            return Err(sqlx::Error::Decode("Invalid page size: 0".into()));
+        }
+        // Query gets wrapped into a subquery, so it can't be terminated
+        let query = hugsqlx::runtime::strip_terminator(sql);
+        let mut conn = executor.acquire().await?;
+        let total = if page.total {
+            let count = hugsqlx::runtime::count_sql(query);
+            let total: i64 = sqlx::query_scalar_with(&count, params.clone())
+                .fetch_one(&mut *conn)
+                .await?;
+            Some(u64::try_from(total).map_err(|e| sqlx::Error::Decode(e.into()))?)
+        } else {
+            None
+        };
+        let limit = page.size.saturating_add(1);
+        #paging
+        let mut rows = sqlx::query_with(&sql, params).fetch_all(&mut *conn).await?;
+        let has_more = rows.len() > page.size;
+        rows.truncate(page.size);
+        let next_cursor = match rows.last() {
+            Some(row) if has_more => Some(#next_cursor),
+            _ => None,
+        };
+        #prelude
+        let mut items = Vec::with_capacity(rows.len());
+        for row in rows {
+            items.push(#value?);
+        }
+        Ok(hugsqlx::Page {
+            items,
+            next_cursor,
+            has_more,
+            total,
+        })
+    });
+    let generics = if generics.is_empty() {
+        quote! { #(#key_types),* }
+    } else {
+        quote! { #generics #(, #key_types)* }
+    };
+    quote! {
+        async fn #name<'q, 'e, 'c, E, #generics> (executor: E, #resolver_args params: #args, page: hugsqlx::PageRequest, #value_args) -> Result<hugsqlx::Page<#ty>, sqlx::Error>
+        where
+              'q: 'e,
+              'c: 'e,
+              E: sqlx::Acquire<'c, Database = #db> + Send + 'e,
+              #(#key_bounds)*
+              #bounds {
+            #body
+        }
+    }
+}
+
+/// Generates companion function of a query, which rather than running the query returns it
+/// configured with resolved SQL and parameters, as `sqlx::query::Query`, `QueryAs`,
+/// `QueryScalar` or `Map`. As sqlx queries borrow their SQL, SQL assembled at runtime gets
+/// resolved into a buffer passed by caller. Queries which can't be represented this way get
+/// no companion: the ones split into chunks, run as multiple statements, checking number of
+/// affected rows or mapped asynchronously.
+fn generate_query_fn(q: &Query, ctx: &Context) -> TokenStream2 {
+    let Context { db, args, row, .. } = ctx;
+    let plain = (
+        quote! {},
+        quote! { sqlx::query::Query<'q, #db, #args> },
+        quote! { sqlx::query_with(sql, params) },
+    );
+    let mapper_bounds = match q.kind {
+        Kind::Mapped => quote! { F: FnMut(#row) -> T + Send, },
+        _ => quote! { F: FnMut(#row) -> Result<T, sqlx::Error> + Send, },
+    };
+    let fetched = match q.kind {
+        Kind::Untyped => plain.clone(),
+        Kind::Typed => (
+            quote! { T: Send + Unpin + for<'r> sqlx::FromRow<'r, #row>, },
+            quote! { sqlx::query::QueryAs<'q, #db, T, #args> },
+            quote! { sqlx::query_as_with(sql, params) },
+        ),
+        Kind::Mapped => (
+            quote! { #mapper_bounds T: Send + Unpin, },
+            quote! { sqlx::query::Map<'q, #db, impl FnMut(#row) -> Result<T, sqlx::Error> + Send, #args> },
+            quote! { sqlx::query_with(sql, params).map(mapper) },
+        ),
+        Kind::TryMapped => (
+            quote! { #mapper_bounds T: Send + Unpin, },
+            quote! { sqlx::query::Map<'q, #db, F, #args> },
+            quote! { sqlx::query_with(sql, params).try_map(mapper) },
+        ),
+        Kind::AsyncMapped => return TokenStream2::new(),
+    };
+    let mapped = matches!(q.kind, Kind::Mapped | Kind::TryMapped);
+    let (bounds, output, query) = match q.method {
+        Method::FetchAll
+        | Method::FetchOne
+        | Method::FetchOptional
+        | Method::FetchMany
+        | Method::FetchChunks(_) => fetched,
+        Method::FetchScalar => (
+            quote! { T: Send + Unpin + for<'r> sqlx::Decode<'r, #db> + sqlx::Type<#db>, },
+            quote! { sqlx::query::QueryScalar<'q, #db, T, #args> },
+            quote! { sqlx::query_scalar_with(sql, params) },
+        ),
+        Method::Execute | Method::ExecuteRaw | Method::RowsAffected | Method::Exists => plain,
+        _ => return TokenStream2::new(),
+    };
+    let resolver = generate_cond_block_resolver_fn(q, ctx);
+    let resolver = generate_identifiers(q, resolver);
+    if resolver.chunks.is_some() || q.affects.is_some() {
+        return TokenStream2::new();
+    }
+    let (generics, mapper) = match (bounds.is_empty(), mapped) {
+        (true, _) => (quote! {}, quote! {}),
+        (false, true) if !matches!(q.method, Method::FetchScalar) => {
+            (quote! { , F, T }, quote! { mapper: F, })
+        }
+        (false, _) => (quote! { , T }, quote! {}),
+    };
+    let name = format_ident!("{}_query", q.name);
+    let resolver_args = &resolver.args;
+    let cfg_attr = generate_cfg_attr(q);
+    match &resolver.sql {
+        Sql::Static(sql) => {
+            let doc = format!("Returns `{}` query to be run later.", q.name);
+            quote! {
+                #cfg_attr
+                #[doc = #doc]
+                fn #name<'q #generics> (#resolver_args params: #args, #mapper) -> #output
+                where
+                      #bounds {
+                    let sql = #sql;
+                    #query
+                }
+            }
+        }
+        Sql::Assembled(assembly) => {
+            let doc = format!(
+                "Returns `{}` query to be run later, with its SQL assembled into `sql` buffer.",
+                q.name
+            );
+            quote! {
+                #cfg_attr
+                #[doc = #doc]
+                fn #name<'q #generics> (sql: &'q mut String, #resolver_args params: #args, #mapper) -> Result<#output, sqlx::Error>
+                where
+                      #bounds {
+                    let (assembled, params) = #assembly?;
+                    *sql = assembled;
+                    let sql = sql.as_str();
+                    Ok(#query)
+                }
+            }
+        }
+    }
+}
+
+/// Splits SQL of a script query into statements run one by one.
+fn generate_statements(query: &Query) -> Vec<String> {
+    match query.sql.as_slice() {
+        [condblock::SqlBlock::Literal(sql)]
+            if !condblock::has_expansions(sql) && !condblock::has_identifier_slots(&query.sql) =>
+        {
+            condblock::split_statements(sql)
+        }
+        _ => panic!(
+            "Script query {} can't contain blocks, expansions nor identifier slots",
+            query.name
+        ),
+    }
+}
+
+/// Extends SQL resolver of a query with identifier slots by `Identifiers` argument,
+/// which assembled SQL takes quoted identifiers from.
+fn generate_identifiers(query: &Query, resolver: SqlResolver) -> SqlResolver {
+    if !condblock::has_identifier_slots(&query.sql) {
+        return resolver;
+    }
+    let args = &resolver.args;
+    SqlResolver {
+        args: quote! { idents: &hugsqlx::Identifiers, #args },
+        ..resolver
+    }
+}
+
+/// Returns names of types generated for a query, along with what each of them is generated
+/// for: enum of conditional blocks along with flags struct, enums of identifiers allowed by
+/// `:ident` declarations and enums of `--~order_by` columns.
+fn generated_types(query: &Query) -> Vec<(String, String)> {
+    let enumeration = snake_to_pascal(&query.name);
+    let mut types = Vec::new();
+    if condblock::has_conditionals(&query.sql) {
+        types.push((
+            enumeration.clone(),
+            format!("conditional blocks of query {}", query.name),
+        ));
+        types.push((
+            format!("{}Flags", enumeration),
+            format!("flags of query {}", query.name),
+        ));
+    }
+    for identifier in query.identifiers.iter().filter(|i| !i.allowed.is_empty()) {
+        types.push((
+            format!("{}{}", enumeration, snake_to_pascal(&identifier.name)),
+            format!(":ident {} of query {}", identifier.name, query.name),
+        ));
+    }
+    for block in condblock::order_bys(&query.sql) {
+        if let condblock::SqlBlock::OrderBy { name, .. } = block {
+            types.push((
+                format!("{}{}", enumeration, snake_to_pascal(name)),
+                format!("--~order_by {} of query {}", name, query.name),
+            ));
+        }
+    }
+    types
+}
+
+fn generate_impl_fns(
+    queries: Vec<Query>,
+    ctx: &Context,
+    functions_ts: &mut TokenStream2,
+    enums_ts: &mut TokenStream2,
+) {
+    let names: BTreeSet<_> = queries.iter().map(|q| q.name.clone()).collect();
+    let mut types = BTreeMap::new();
+    for (ty, origin) in queries.iter().flat_map(generated_types) {
+        if let Some(other) = types.insert(ty.clone(), origin.clone()) {
+            panic!(
+                "Type {} generated for {} collides with the one generated for {}, rename one of them",
+                ty, origin, other
+            );
+        }
+    }
+    for q in queries {
+        let builder = generate_query_fn(&q, ctx);
+        if !builder.is_empty() && names.contains(&format!("{}_query", q.name)) {
+            panic!(
+                "Query builder of query {} collides with query {}_query, rename one of them",
+                q.name, q.name
+            );
+        }
+        if let Some(doc) = &q.doc {
+            functions_ts.extend(quote! { #[doc = #doc] });
+        }
+        functions_ts.extend(generate_cfg_attr(&q));
+        match q.kind {
+            Kind::Typed => generate_typed_fn(q, ctx, functions_ts, enums_ts),
+            Kind::Untyped => generate_untyped_fn(q, ctx, functions_ts, enums_ts),
+            Kind::Mapped | Kind::TryMapped | Kind::AsyncMapped => {
+                generate_mapped_fn(q, ctx, functions_ts, enums_ts)
+            }
+        }
+        functions_ts.extend(builder);
+    }
+}
+
+fn generate_typed_fn(
+    q: Query,
+    ctx: &Context,
+    functions_ts: &mut TokenStream2,
+    enums_ts: &mut TokenStream2,
+) {
+    let Context {
+        db,
+        args,
+        row,
+        result,
+        limit,
+        ..
+    } = ctx;
+    let name = Ident::new(&q.name, Span::call_site());
+    let resolver = generate_cond_block_resolver_fn(&q, ctx);
+    let resolver = generate_identifiers(&q, resolver);
+    let resolver_args = &resolver.args;
+
+    enums_ts.extend(resolver.enums.clone());
 
     functions_ts.extend(match q.method {
-        Method::FetchMany => {
+        Method::FetchMany | Method::FetchChunks(_) => {
+            let body = resolver.stream(quote! {
+                sqlx::query_as_with(sql, params).fetch(executor)
+            });
+            let (item, body) = generate_chunks(&q, quote! { T }, body);
             quote! {
-                async fn #name<'q, 'e, 'c, E, F, T> (executor: E, #block_resolver params: #args, mapper: F) -> futures_core::stream::BoxStream<'e, Result<T, sqlx::Error>>
+                async fn #name<'q, 'e, 'c, E, T> (executor: E, #resolver_args params: #args) -> futures_core::stream::BoxStream<'e, #item>
                 where
                       'q: 'e,
                       'c: 'e,
                       E: sqlx::Executor<'c, Database = #db> + 'e,
-                      F: FnMut(#row) -> T + Send + 'e,
-                      T: Send + Unpin + 'e {
-                    sqlx::query_with(#sql, params)
-                        .map(mapper)
-                        .fetch(executor)
+                      T: Send + Unpin + for<'r> sqlx::FromRow<'r, #row> + 'e {
+                    #body
+                }
+            }
+        },
+        Method::FetchOne => {
+            let body = resolver.call(quote! {
+                sqlx::query_as_with(sql, params).fetch_one(executor).await
+            });
+            quote! {
+                async fn #name<'q, 'e, 'c, E, T> (executor: E, #resolver_args params: #args) -> Result<T, sqlx::Error>
+                where
+                      'q: 'e,
+                      'c: 'e,
+                      E: sqlx::Executor<'c, Database = #db> + 'e,
+                      T: Send + Unpin + for<'r> sqlx::FromRow<'r, #row> + 'e {
+                    #body
+                }
+            }
+        },
+        Method::FetchOptional => {
+            let body = resolver.call(quote! {
+                sqlx::query_as_with(sql, params).fetch_optional(executor).await
+            });
+            quote! {
+                async fn #name<'q, 'e, 'c, E, T> (executor: E, #resolver_args params: #args) -> Result<Option<T>, sqlx::Error>
+                where
+                      'q: 'e,
+                      'c: 'e,
+                      E: sqlx::Executor<'c, Database = #db> + 'e,
+                      T: Send + Unpin + for<'r> sqlx::FromRow<'r, #row> + 'e {
+                    #body
+                }
+            }
+        },
+        Method::FetchAll => {
+            let executor = resolver.executor(db);
+            let body = resolver.call_chunked(
+                quote! { sqlx::query_as_with(sql, params).fetch_all(executor).await },
+                quote! { Vec::new() },
+                false,
+                *limit,
+            );
+            quote! {
+                async fn #name<'q, 'e, 'c, E, T> (executor: E, #resolver_args params: #args) -> Result<Vec<T>, sqlx::Error>
+                where
+                     'q: 'e,
+                     'c: 'e,
+                      E: #executor + 'e,
+                      T: Send + Unpin + for<'r> sqlx::FromRow<'r, #row> + 'e {
+                    #body
+                }
+            }
+        },
+        Method::Execute | Method::ExecuteRaw => {
+            let executor = resolver.executor(db);
+            let body = resolver.call_chunked(
+                quote! { sqlx::query_with(sql, params).execute(executor).await },
+                quote! { #result::default() },
+                true,
+                *limit,
+            );
+            let (executor, body, error) = generate_affects(&q, executor, body, db, result);
+            let (output, body) = generate_outcome(&q, body, &error, ctx);
+            quote! {
+                async fn #name<'q, 'e, 'c, E> (executor: E, #resolver_args params: #args) -> Result<#output, #error>
+                where
+                 'q: 'e,
+                 'c: 'e,
+                 E: #executor + 'e {
+                    #body
+                }
+            }
+        },
+        Method::Bulk | Method::Batch | Method::Script => {
+            panic!("Bulk, batch or script query {} can't be typed nor mapped", q.name)
+        }
+        Method::FetchScalar | Method::Exists | Method::RowsAffected => generate_kindless_fn(&q, &resolver, ctx),
+        Method::Map(_)
+        | Method::Group(_)
+        | Method::Nest(..)
+        | Method::Cursor(_)
+        | Method::Paged => {
+            let nested = match q.method {
+                Method::Nest(..) => quote! { T: hugsqlx::Nest, T::Child: hugsqlx::NestChild<#row>, },
+                _ => TokenStream2::new(),
+            };
+            let value = RowValue {
+                ty: quote! { T },
+                generics: quote! { T },
+                bounds: quote! {
+                    T: Send + Unpin + for<'r> sqlx::FromRow<'r, #row> + 'e,
+                    #nested
+                },
+                args: TokenStream2::new(),
+                prelude: TokenStream2::new(),
+                value: quote! { <T as sqlx::FromRow<#row>>::from_row(&row) },
+            };
+            match q.method {
+                Method::Cursor(_) => generate_cursor_fn(&q, &resolver, ctx, value),
+                Method::Paged => generate_paged_fn(&q, &resolver, ctx, value),
+                _ => generate_grouped_fn(&q, &resolver, ctx, value),
+            }
+        }
+    });
+}
+
+fn generate_untyped_fn(
+    q: Query,
+    ctx: &Context,
+    functions_ts: &mut TokenStream2,
+    enums_ts: &mut TokenStream2,
+) {
+    let Context {
+        db,
+        args,
+        row,
+        result,
+        limit,
+        arrays,
+        ..
+    } = ctx;
+    let name = Ident::new(&q.name, Span::call_site());
+    let resolver = generate_cond_block_resolver_fn(&q, ctx);
+    let resolver = generate_identifiers(&q, resolver);
+    let resolver_args = &resolver.args;
+
+    enums_ts.extend(resolver.enums.clone());
+
+    functions_ts.extend(match q.method {
+        Method::FetchMany | Method::FetchChunks(_) => {
+            let body = resolver.stream(quote! {
+                sqlx::query_with(sql, params).fetch(executor)
+            });
+            let (item, body) = generate_chunks(&q, quote! { #row }, body);
+            quote! {
+                async fn #name<'q, 'e, 'c, E> (executor: E, #resolver_args params: #args) -> futures_core::stream::BoxStream<'e, #item>
+                where
+                 'q: 'e,
+                 'c: 'e,
+                 E: sqlx::Executor<'c, Database = #db> + 'e {
+                    #body
                 }
             }
         },
         Method::FetchOne => {
+            let body = resolver.call(quote! {
+                sqlx::query_with(sql, params).fetch_one(executor).await
+            });
             quote! {
-                async fn #name<'q, 'e, 'c, E, F, T> (executor: E, #block_resolver params: #args, mapper: F) -> Result<T, sqlx::Error>
+                async fn #name<'q, 'e, 'c, E> (executor: E, #resolver_args params: #args) -> Result<#row, sqlx::Error>
+                where
+                 'q: 'e,
+                 'c: 'e,
+                 E: sqlx::Executor<'c, Database = #db> + 'e {
+                    #body
+                }
+            }
+        },
+        Method::FetchOptional => {
+            let body = resolver.call(quote! {
+                sqlx::query_with(sql, params).fetch_optional(executor).await
+            });
+            quote! {
+                async fn #name<'q, 'e, 'c, E> (executor: E, #resolver_args params: #args) -> Result<Option<#row>, sqlx::Error>
+                where
+                 'q: 'e,
+                 'c: 'e,
+                 E: sqlx::Executor<'c, Database = #db> + 'e {
+                    #body
+                }
+            }
+        },
+        Method::FetchAll => {
+            let executor = resolver.executor(db);
+            let body = resolver.call_chunked(
+                quote! { sqlx::query_with(sql, params).fetch_all(executor).await },
+                quote! { Vec::new() },
+                false,
+                *limit,
+            );
+            quote! {
+                async fn #name<'q, 'e, 'c, E> (executor: E, #resolver_args params: #args) -> Result<Vec<#row>, sqlx::Error>
+                where
+                 'q: 'e,
+                 'c: 'e,
+                 E: #executor + 'e {
+                    #body
+                }
+            }
+        },
+        Method::Execute | Method::ExecuteRaw => {
+            let executor = resolver.executor(db);
+            let body = resolver.call_chunked(
+                quote! { sqlx::query_with(sql, params).execute(executor).await },
+                quote! { #result::default() },
+                true,
+                *limit,
+            );
+            let (executor, body, error) = generate_affects(&q, executor, body, db, result);
+            let (output, body) = generate_outcome(&q, body, &error, ctx);
+            quote! {
+                async fn #name<'q, 'e, 'c, E> (executor: E, #resolver_args params: #args) -> Result<#output, #error>
+                where
+                 'q: 'e,
+                 'c: 'e,
+                 E: #executor + 'e {
+                    #body
+                }
+            }
+        },
+        Method::Bulk => {
+            if !arrays {
+                panic!("Bulk query {} requires a database binding arrays, like postgres", q.name);
+            }
+            let (rows, ty, binds) = generate_bulk_columns(&q);
+            let body = resolver.call(quote! {
+                sqlx::query_with(sql, params).execute(executor).await
+            });
+            let (output, body) = generate_outcome(&q, body, &quote! { sqlx::Error }, ctx);
+            quote! {
+                async fn #name<'q, 'e, 'c, E> (executor: E, #rows: &'q [#ty], #resolver_args params: #args) -> Result<#output, sqlx::Error>
+                where
+                 'q: 'e,
+                 'c: 'e,
+                 E: sqlx::Executor<'c, Database = #db> + 'e {
+                    use sqlx::Arguments;
+                    let mut params = params;
+                    #binds
+                    #body
+                }
+            }
+        },
+        Method::Batch => {
+            let body = resolver.call(quote! {
+                sqlx::query_with(sql, params).execute(executor).await
+            });
+            quote! {
+                async fn #name<'q, 'e, 'c, E, I> (executor: E, #resolver_args params: I) -> Result<u64, hugsqlx::BatchError<sqlx::Error>>
+                where
+                 'q: 'e,
+                 'c: 'e,
+                 E: sqlx::Acquire<'c, Database = #db> + Send + 'e,
+                 I: IntoIterator<Item = #args> {
+                    use sqlx::Acquire;
+                    let mut tx = executor.begin().await.map_err(hugsqlx::BatchError::transaction)?;
+                    let mut rows_affected = 0;
+                    for (index, params) in params.into_iter().enumerate() {
+                        let executor = &mut *tx;
+                        let result: Result<#result, sqlx::Error> = async { #body }.await;
+                        match result {
+                            Ok(result) => rows_affected += result.rows_affected(),
+                            // Dropped transaction gets rolled back
+                            Err(error) => return Err(hugsqlx::BatchError { index: Some(index), error }),
+                        }
+                    }
+                    tx.commit().await.map_err(hugsqlx::BatchError::transaction)?;
+                    Ok(rows_affected)
+                }
+            }
+        },
+        Method::Script => {
+            let statements = generate_statements(&q);
+            let run = |conn: TokenStream2| {
+                quote! {
+                    for sql in STATEMENTS {
+                        let result = sqlx::raw_sql(sql).execute(&mut *#conn).await?;
+                        results.push(hugsqlx::StatementResult { sql, result });
+                    }
+                }
+            };
+            let (run_tx, run_conn) = (run(quote! { tx }), run(quote! { conn }));
+            quote! {
+                async fn #name<'c, E> (executor: E, transaction: bool) -> Result<Vec<hugsqlx::StatementResult<#result>>, sqlx::Error>
+                where
+                 E: sqlx::Acquire<'c, Database = #db> + Send {
+                    use sqlx::Acquire;
+                    const STATEMENTS: &[&str] = &[#(#statements),*];
+                    let mut results = Vec::with_capacity(STATEMENTS.len());
+                    if transaction {
+                        let mut tx = executor.begin().await?;
+                        #run_tx
+                        tx.commit().await?;
+                    } else {
+                        let mut conn = executor.acquire().await?;
+                        #run_conn
+                    }
+                    Ok(results)
+                }
+            }
+        },
+        Method::FetchScalar | Method::Exists | Method::RowsAffected => generate_kindless_fn(&q, &resolver, ctx),
+        Method::Map(_) | Method::Group(_) | Method::Cursor(_) | Method::Paged => {
+            let value = RowValue {
+                ty: quote! { #row },
+                generics: TokenStream2::new(),
+                bounds: TokenStream2::new(),
+                args: TokenStream2::new(),
+                prelude: TokenStream2::new(),
+                value: quote! { Ok::<_, sqlx::Error>(row) },
+            };
+            match q.method {
+                Method::Cursor(_) => generate_cursor_fn(&q, &resolver, ctx, value),
+                Method::Paged => generate_paged_fn(&q, &resolver, ctx, value),
+                _ => generate_grouped_fn(&q, &resolver, ctx, value),
+            }
+        }
+        Method::Nest(..) => panic!("Nest query {} needs to be typed", q.name),
+    });
+}
+
+fn generate_mapped_fn(
+    q: Query,
+    ctx: &Context,
+    functions_ts: &mut TokenStream2,
+    enums_ts: &mut TokenStream2,
+) {
+    let Context {
+        db,
+        args,
+        row,
+        result,
+        limit,
+        ..
+    } = ctx;
+    let name = Ident::new(&q.name, Span::call_site());
+    let resolver = generate_cond_block_resolver_fn(&q, ctx);
+    let resolver = generate_identifiers(&q, resolver);
+    let resolver_args = &resolver.args;
+
+    enums_ts.extend(resolver.enums.clone());
+
+    // Fallible mapper is handed over to sqlx's `try_map`, while async one gets called
+    // and awaited upon each of fetched rows.
+    let asynchronous = q.kind == Kind::AsyncMapped;
+    let (map, generics, mapper_bounds) = match q.kind {
+        Kind::TryMapped => (
+            quote! { try_map },
+            quote! { F, T },
+            quote! { F: FnMut(#row) -> Result<T, sqlx::Error> + Send + 'e, },
+        ),
+        Kind::AsyncMapped => (
+            quote! { map },
+            quote! { F, Fut, T },
+            quote! {
+                F: FnMut(#row) -> Fut + Send + 'e,
+                Fut: std::future::Future<Output = Result<T, sqlx::Error>> + Send + 'e,
+            },
+        ),
+        _ => (
+            quote! { map },
+            quote! { F, T },
+            quote! { F: FnMut(#row) -> T + Send + 'e, },
+        ),
+    };
+
+    functions_ts.extend(match q.method {
+        Method::FetchMany | Method::FetchChunks(_) => {
+            let body = if asynchronous {
+                let stream = resolver.stream_each(
+                    quote! { sqlx::query_with(sql, params).fetch(executor) },
+                    quote! { mapper(row).await },
+                );
+                quote! {
+                    let mut mapper = mapper;
+                    #stream
+                }
+            } else {
+                resolver.stream(quote! {
+                    sqlx::query_with(sql, params)
+                        .#map(mapper)
+                        .fetch(executor)
+                })
+            };
+            let (item, body) = generate_chunks(&q, quote! { T }, body);
+            quote! {
+                async fn #name<'q, 'e, 'c, E, #generics> (executor: E, #resolver_args params: #args, mapper: F) -> futures_core::stream::BoxStream<'e, #item>
                 where
                       'q: 'e,
                       'c: 'e,
                       E: sqlx::Executor<'c, Database = #db> + 'e,
-                      F: FnMut(#row) -> T + Send + 'e,
+                      #mapper_bounds
                       T: Send + Unpin + 'e {
-                    sqlx::query_with(#sql, params)
-                        .map(mapper)
+                    #body
+                }
+            }
+        },
+        Method::FetchOne => {
+            let body = if asynchronous {
+                resolver.call(quote! {
+                    let mut mapper = mapper;
+                    let row = sqlx::query_with(sql, params).fetch_one(executor).await?;
+                    mapper(row).await
+                })
+            } else {
+                resolver.call(quote! {
+                    sqlx::query_with(sql, params)
+                        .#map(mapper)
                         .fetch_one(executor)
                         .await
+                })
+            };
+            quote! {
+                async fn #name<'q, 'e, 'c, E, #generics> (executor: E, #resolver_args params: #args, mapper: F) -> Result<T, sqlx::Error>
+                where
+                      'q: 'e,
+                      'c: 'e,
+                      E: sqlx::Executor<'c, Database = #db> + 'e,
+                      #mapper_bounds
+                      T: Send + Unpin + 'e {
+                    #body
                 }
             }
         },
         Method::FetchOptional => {
+            let body = if asynchronous {
+                resolver.call(quote! {
+                    let mut mapper = mapper;
+                    match sqlx::query_with(sql, params).fetch_optional(executor).await? {
+                        Some(row) => mapper(row).await.map(Some),
+                        None => Ok(None),
+                    }
+                })
+            } else {
+                resolver.call(quote! {
+                    sqlx::query_with(sql, params)
+                        .#map(mapper)
+                        .fetch_optional(executor)
+                        .await
+                })
+            };
             quote! {
-                async fn #name<'q, 'e, 'c, E, F, T> (executor: E, #block_resolver params: #args, mapper: F) -> Result<Option<T>, sqlx::Error>
+                async fn #name<'q, 'e, 'c, E, #generics> (executor: E, #resolver_args params: #args, mapper: F) -> Result<Option<T>, sqlx::Error>
                 where
                       'q: 'e,
                       'c: 'e,
                       E: sqlx::Executor<'c, Database = #db> + 'e,
-                      F: FnMut(#row) -> T + Send + 'e,
+                      #mapper_bounds
                       T: Send + Unpin + 'e {
-                    sqlx::query_with(#sql, params)
-                        .map(mapper)
-                        .fetch_optional(executor)
-                        .await
+                    #body
                 }
             }
         },
         Method::FetchAll => {
+            let executor = resolver.executor(db);
+            // Mapper gets reused by all the chunks
+            let (rebind, mapper) = match resolver.chunks {
+                Some(_) => (quote! { let mut mapper = mapper; }, quote! { &mut mapper }),
+                None if asynchronous => (quote! { let mut mapper = mapper; }, quote! { mapper }),
+                None => (TokenStream2::new(), quote! { mapper }),
+            };
+            let query = if asynchronous {
+                quote! {
+                    {
+                        let rows = sqlx::query_with(sql, params).fetch_all(executor).await?;
+                        let mut mapped = Vec::with_capacity(rows.len());
+                        for row in rows {
+                            mapped.push(mapper(row).await?);
+                        }
+                        Ok::<_, sqlx::Error>(mapped)
+                    }
+                }
+            } else {
+                quote! {
+                    sqlx::query_with(sql, params)
+                        .#map(#mapper)
+                        .fetch_all(executor)
+                        .await
+                }
+            };
+            let body = resolver.call_chunked(query, quote! { Vec::new() }, false, *limit);
             quote! {
-                async fn #name<'q, 'e, 'c, E, F, T> (executor: E, #block_resolver params: #args, mapper: F) -> Result<Vec<T>, sqlx::Error>
+                async fn #name<'q, 'e, 'c, E, #generics> (executor: E, #resolver_args params: #args, mapper: F) -> Result<Vec<T>, sqlx::Error>
                 where
                       'q: 'e,
                       'c: 'e,
-                      E: sqlx::Executor<'c, Database = #db> + 'e,
-                      F: FnMut(#row) -> T + Send + 'e,
+                      E: #executor + 'e,
+                      #mapper_bounds
                       T: Send + Unpin + 'e {
-                    sqlx::query_with(#sql, params)
-                        .map(mapper)
-                        .fetch_all(executor)
-                        .await
+                    #rebind
+                    #body
                 }
             }
         },
-        Method::Execute => {
+        Method::Execute | Method::ExecuteRaw => {
+            let executor = resolver.executor(db);
+            let body = resolver.call_chunked(
+                quote! { sqlx::query_with(sql, params).execute(executor).await },
+                quote! { #result::default() },
+                true,
+                *limit,
+            );
+            let (executor, body, error) = generate_affects(&q, executor, body, db, result);
+            let (output, body) = generate_outcome(&q, body, &error, ctx);
             quote! {
-                async fn #name<'q, 'e, 'c, E, F, T> (executor: E, #block_resolver params: #args) -> Result<#result, sqlx::Error>
+                async fn #name<'q, 'e, 'c, E, F, T> (executor: E, #resolver_args params: #args) -> Result<#output, #error>
                 where
                       'q: 'e,
                       'c: 'e,
-                      E: sqlx::Executor<'c, Database = #db> + 'e {
-                    sqlx::query_with(#sql, params).execute(executor).await
+                      E: #executor + 'e {
+                    #body
                 }
             }
         },
+        Method::Bulk | Method::Batch | Method::Script => {
+            panic!("Bulk, batch or script query {} can't be typed nor mapped", q.name)
+        }
+        Method::Exists | Method::RowsAffected => generate_kindless_fn(&q, &resolver, ctx),
+        Method::FetchScalar => panic!("Scalar query {} can't be mapped", q.name),
+        Method::Map(_) | Method::Group(_) | Method::Cursor(_) | Method::Paged => {
+            let value = match q.kind {
+                Kind::TryMapped => quote! { mapper(row) },
+                Kind::AsyncMapped => quote! { mapper(row).await },
+                _ => quote! { Ok::<_, sqlx::Error>(mapper(row)) },
+            };
+            let value = RowValue {
+                ty: quote! { T },
+                generics,
+                bounds: quote! {
+                    #mapper_bounds
+                    T: Send + Unpin + 'e,
+                },
+                args: quote! { mapper: F },
+                prelude: quote! { let mut mapper = mapper; },
+                value,
+            };
+            match q.method {
+                Method::Cursor(_) => generate_cursor_fn(&q, &resolver, ctx, value),
+                Method::Paged => generate_paged_fn(&q, &resolver, ctx, value),
+                _ => generate_grouped_fn(&q, &resolver, ctx, value),
+            }
+        }
+        Method::Nest(..) => panic!("Nest query {} needs to be typed", q.name),
     });
 }
 
@@ -537,8 +2622,19 @@ cfg_if::cfg_if! {
 
 #[cfg(test)]
 mod test {
-    use crate::parser::{query_parser, Kind, Method};
-    use chumsky::Parser;
+    use crate::parser::{self, Fields, Identifier, Kind, Method, OrderKey, Param, Query};
+    use crate::{
+        condblock, generate_impl_fns, precompute_sql, Context, ContextType, MAX_PRECOMPUTED_BLOCKS,
+        MAX_PRECOMPUTED_CFGS,
+    };
+    use chumsky::{error::Simple, Parser};
+    use proc_macro2::TokenStream as TokenStream2;
+    use quote::format_ident;
+
+    /// Parser of queries of a single input, with snippets it defines expanded.
+    fn query_parser() -> impl Parser<char, Vec<Query>, Error = Simple<char>> {
+        parser::query_parser().map(parser::expand_snippets)
+    }
 
     #[test]
     fn parsing_defaults() {
@@ -604,6 +2700,132 @@ SELECT user_id, email, name, picture FROM users
         assert_eq!(queries[0].method, Method::Execute);
     }
 
+    #[test]
+    fn parsing_fallible_mappers() {
+        let input = r#"
+-- :name fetch_user :try_mapped :1
+SELECT user_id, email, name, picture FROM users WHERE user_id = $1
+-- :name fetch_users :async_mapped :*
+SELECT user_id, email, name, picture FROM users
+"#;
+
+        let queries = query_parser().parse(input).unwrap();
+        assert_eq!(queries.len(), 2);
+        assert_eq!(queries[0].kind, Kind::TryMapped);
+        assert_eq!(queries[0].method, Method::FetchOne);
+        assert_eq!(queries[1].kind, Kind::AsyncMapped);
+        assert_eq!(queries[1].method, Method::FetchAll);
+    }
+
+    #[test]
+    fn parsing_grouping() {
+        let input = r#"
+-- :name users_by_id :typed :map user_id
+SELECT user_id, name FROM users
+-- :name users_by_role :group role
+SELECT role, user_id, name FROM users
+-- :name users_with_roles :typed :nest user_id role_
+SELECT u.user_id, u.name, r.id AS role_id FROM users u LEFT JOIN roles r USING(user_id)
+"#;
+
+        let queries = query_parser().parse(input).unwrap();
+        assert_eq!(queries.len(), 3);
+        assert_eq!(queries[0].method, Method::Map("user_id".to_string()));
+        assert_eq!(queries[1].kind, Kind::Untyped);
+        assert_eq!(queries[1].method, Method::Group("role".to_string()));
+        assert_eq!(
+            queries[2].method,
+            Method::Nest("user_id".to_string(), "role_".to_string())
+        );
+        assert_eq!(
+            queries[2].sql,
+            vec![condblock::SqlBlock::Literal(
+                "SELECT u.user_id, u.name, r.id AS role_id FROM users u LEFT JOIN roles r USING(user_id)"
+                    .to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn parsing_chunks() {
+        let input = r#"
+-- :name export_users :typed :^500
+SELECT user_id, name FROM users
+-- :name export_roles :chunks 100
+SELECT role FROM roles
+"#;
+
+        let queries = query_parser().parse(input).unwrap();
+        assert_eq!(queries.len(), 2);
+        assert_eq!(queries[0].method, Method::FetchChunks(500));
+        assert_eq!(queries[1].kind, Kind::Untyped);
+        assert_eq!(queries[1].method, Method::FetchChunks(100));
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid size of chunks: 0")]
+    fn parsing_empty_chunks() {
+        let input = r#"
+-- :name export_users :typed :^0
+SELECT user_id, name FROM users
+"#;
+        query_parser().parse(input).unwrap();
+    }
+
+    #[test]
+    fn parsing_cursor() {
+        let input = r#"
+-- :name export_users :typed :cursor 1000
+SELECT user_id, name FROM users
+"#;
+
+        let queries = query_parser().parse(input).unwrap();
+        assert_eq!(queries.len(), 1);
+        assert_eq!(queries[0].kind, Kind::Typed);
+        assert_eq!(queries[0].method, Method::Cursor(1000));
+    }
+
+    #[test]
+    fn parsing_paged() {
+        let input = r#"
+-- :name users_page :typed :paged
+-- :order_key created_at DESC, user_id asc
+SELECT user_id, created_at FROM users
+-- :name roles_page :paged
+SELECT role FROM roles ORDER BY role
+"#;
+
+        let queries = query_parser().parse(input).unwrap();
+        assert_eq!(queries.len(), 2);
+        assert_eq!(queries[0].method, Method::Paged);
+        assert_eq!(
+            queries[0].order_key,
+            vec![
+                OrderKey {
+                    column: "created_at".to_string(),
+                    descending: true,
+                },
+                OrderKey {
+                    column: "user_id".to_string(),
+                    descending: false,
+                },
+            ]
+        );
+        assert_eq!(queries[1].method, Method::Paged);
+        assert!(queries[1].order_key.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = ":order_key of query users requires :paged hint")]
+    fn parsing_order_key_without_paged() {
+        let input = r#"
+-- :name users :typed :*
+-- :order_key user_id
+SELECT user_id FROM users
+"#;
+        query_parser().parse(input).unwrap();
+    }
+
     #[test]
     fn parsing_multiple() {
         let input = r#"
@@ -662,4 +2884,518 @@ DELETE FROM users
         assert_eq!(queries[3].kind, Kind::Typed);
         assert_eq!(queries[3].method, Method::FetchOne);
     }
+
+    #[test]
+    fn parsing_params() {
+        let input = r#"
+-- :name fetch_users :<> :*
+-- :doc Returns users with given email.
+-- Returns all the users if no email provided.
+-- :param email Option<String>
+SELECT user_id, email, name, picture FROM users
+--~{ ?email
+WHERE email = :email
+--~}
+"#;
+
+        let queries = query_parser().parse(input).unwrap();
+        assert_eq!(queries.len(), 1);
+        assert_eq!(
+            queries[0].doc,
+            Some(
+                "Returns users with given email.\nReturns all the users if no email provided."
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            queries[0].params,
+            vec![Param {
+                name: "email".to_string(),
+                ty: "Option<String>".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn parsing_identifiers() {
+        let input = r#"
+-- :name fetch_users :<> :*
+-- :ident sort_column in (name, created_at)
+-- :ident group_column
+SELECT * FROM users ORDER BY :i:sort_column
+"#;
+
+        let queries = query_parser().parse(input).unwrap();
+        assert_eq!(queries.len(), 1);
+        assert_eq!(
+            queries[0].identifiers,
+            vec![
+                Identifier {
+                    name: "sort_column".to_string(),
+                    allowed: vec!["name".to_string(), "created_at".to_string()]
+                },
+                Identifier {
+                    name: "group_column".to_string(),
+                    allowed: vec![]
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn parsing_bulk() {
+        let input = r#"
+-- :name insert_users :bulk
+-- :param users &[User]
+-- :fields users (user_id, name)
+INSERT INTO users(user_id, name) SELECT * FROM UNNEST($1::int[], $2::text[])
+"#;
+
+        let queries = query_parser().parse(input).unwrap();
+        assert_eq!(queries.len(), 1);
+        assert_eq!(queries[0].kind, Kind::Untyped);
+        assert_eq!(queries[0].method, Method::Bulk);
+        assert_eq!(
+            queries[0].fields,
+            Some(Fields {
+                name: "users".to_string(),
+                fields: vec!["user_id".to_string(), "name".to_string()]
+            })
+        );
+    }
+
+    #[test]
+    fn parsing_result_hints() {
+        let input = r#"
+-- :name count_users :scalar
+SELECT count(*) FROM users
+-- :name user_exists :<> :exists
+SELECT 1 FROM users WHERE user_id = $1
+-- :name rename_user :n
+UPDATE users SET name = $1 WHERE user_id = $2
+"#;
+
+        let queries = query_parser().parse(input).unwrap();
+        assert_eq!(queries.len(), 3);
+        assert_eq!(queries[0].method, Method::FetchScalar);
+        assert_eq!(queries[1].kind, Kind::Typed);
+        assert_eq!(queries[1].method, Method::Exists);
+        assert_eq!(queries[2].method, Method::RowsAffected);
+    }
+
+    #[test]
+    fn parsing_affects() {
+        let input = r#"
+-- :name update_user :! :affects 1
+UPDATE users SET name = $1 WHERE user_id = $2
+-- :name update_users :n :affects 0..=2
+UPDATE users SET name = $1
+-- :name delete_users :affects 1..
+DELETE FROM users
+-- :name delete_user :affects 0..2
+DELETE FROM users WHERE user_id = $1
+"#;
+
+        let queries = query_parser().parse(input).unwrap();
+        let affects: Vec<_> = queries
+            .iter()
+            .map(|q| q.affects.clone().map(|a| (a.min, a.max)))
+            .collect();
+        assert_eq!(
+            affects,
+            vec![
+                Some((1, Some(1))),
+                Some((0, Some(2))),
+                Some((1, None)),
+                Some((0, Some(1)))
+            ]
+        );
+        assert_eq!(queries[1].method, Method::RowsAffected);
+    }
+
+    #[test]
+    #[should_panic(expected = "Empty range of affected rows: 2..=1")]
+    fn parsing_empty_affects() {
+        let input = "-- :name update_user :affects 2..=1\nUPDATE users SET name = $1";
+        let _ = query_parser().parse(input);
+    }
+
+    #[test]
+    fn parsing_batch() {
+        let input = r#"
+-- :name insert_users :batch
+INSERT INTO users(user_id, name) VALUES($1, $2)
+"#;
+
+        let queries = query_parser().parse(input).unwrap();
+        assert_eq!(queries.len(), 1);
+        assert_eq!(queries[0].kind, Kind::Untyped);
+        assert_eq!(queries[0].method, Method::Batch);
+    }
+
+    #[test]
+    #[should_panic(expected = ":fields mapping of query insert_users requires :bulk hint")]
+    fn parsing_fields_without_bulk() {
+        let input = r#"
+-- :name insert_users
+-- :fields users (user_id, name)
+INSERT INTO users(user_id, name) SELECT * FROM UNNEST($1::int[], $2::text[])
+"#;
+
+        let _ = query_parser().parse(input);
+    }
+
+    #[test]
+    fn parsing_snippets() {
+        let snippets = r#"
+-- :snippet user_columns
+user_id, email, name
+-- :snippet active_users
+--~include user_columns
+FROM users WHERE active
+"#;
+        let input = r#"
+-- :name fetch_users :<> :*
+SELECT
+--~include active_users
+ORDER BY name
+"#;
+
+        let mut definitions = parser::query_parser().parse(snippets).unwrap();
+        definitions.extend(parser::query_parser().parse(input).unwrap());
+        let queries = parser::expand_snippets(definitions);
+        assert_eq!(queries.len(), 1);
+        assert_eq!(
+            queries[0].sql,
+            vec![condblock::SqlBlock::Literal(
+                "SELECT\nuser_id, email, name\nFROM users WHERE active\nORDER BY name".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Cyclic inclusion of snippet a: a -> b -> a")]
+    fn parsing_cyclic_snippets() {
+        let input = r#"
+-- :snippet a
+--~include b
+-- :snippet b
+--~include a
+-- :name fetch_users :<> :*
+SELECT * FROM users
+--~include a
+"#;
+
+        let _ = query_parser().parse(input);
+    }
+
+    /// Generates functions of given queries for sqlite, as a string.
+    fn generate(input: &str) -> String {
+        let mut functions = TokenStream2::new();
+        let mut enums = TokenStream2::new();
+        let queries = query_parser().parse(input).unwrap();
+        let ctx = Context::new(ContextType::Sqlite);
+        generate_impl_fns(queries, &ctx, &mut functions, &mut enums);
+        functions.to_string()
+    }
+
+    /// Generates types of given queries for sqlite, as a string.
+    fn generate_types(input: &str) -> String {
+        let mut functions = TokenStream2::new();
+        let mut enums = TokenStream2::new();
+        let queries = query_parser().parse(input).unwrap();
+        let ctx = Context::new(ContextType::Sqlite);
+        generate_impl_fns(queries, &ctx, &mut functions, &mut enums);
+        enums.to_string()
+    }
+
+    #[test]
+    fn flags_struct() {
+        let input = r#"
+-- :name fetch_users :<> :*
+SELECT * FROM users
+--~where
+--~{ active
+active
+--~}
+--~{ named
+name IS NOT NULL
+--~}
+--~end
+"#;
+
+        let types = generate_types(input);
+        // Blocks are excluded by default
+        assert!(types.contains(
+            "# [derive (Debug , Clone , Copy , Default , PartialEq , Eq , Hash)] pub struct FetchUsersFlags { pub active : bool , pub named : bool , }"
+        ));
+        assert!(types.contains(
+            "pub fn active (mut self , included : bool) -> Self { self . active = included ; self }"
+        ));
+        assert!(types.contains(
+            "pub fn named (mut self , included : bool) -> Self { self . named = included ; self }"
+        ));
+        assert!(types.contains(
+            "impl hugsqlx :: BlockResolver < FetchUsers > for FetchUsersFlags { fn resolve (& self , block : FetchUsers) -> bool { match block { FetchUsers :: Active => self . active , FetchUsers :: Named => self . named , } } }"
+        ));
+        assert!(generate(input)
+            .contains("block_resolver : impl hugsqlx :: BlockResolver < FetchUsers > + Send"));
+    }
+
+    #[test]
+    fn no_flags_struct_without_conditional_blocks() {
+        let input = r#"
+-- :name fetch_users :<> :*
+-- :param name Option<String>
+SELECT * FROM users
+--~{ ?name
+WHERE name = :name
+--~}
+"#;
+
+        assert!(!generate_types(input).contains("Flags"));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Type FetchUsersFlags generated for :ident flags of query fetch_users collides with the one generated for flags of query fetch_users"
+    )]
+    fn flags_collide_with_identifiers() {
+        let input = r#"
+-- :name fetch_users :<> :*
+-- :ident flags in (active, admin)
+SELECT * FROM users WHERE :i:flags
+--~{ limited
+LIMIT 1
+--~}
+"#;
+
+        generate_types(input);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Type FetchUsersFlags generated for --~order_by flags of query fetch_users collides with the one generated for flags of query fetch_users"
+    )]
+    fn flags_collide_with_sort() {
+        let input = r#"
+-- :name fetch_users :<> :*
+SELECT * FROM users
+--~{ active
+WHERE active
+--~}
+--~order_by flags { name, email }
+"#;
+
+        generate_types(input);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Type FetchUsersFlags generated for flags of query fetch_users collides with the one generated for conditional blocks of query fetch_users_flags"
+    )]
+    fn flags_collide_across_queries() {
+        let input = r#"
+-- :name fetch_users_flags :<> :*
+SELECT * FROM users
+--~{ active
+WHERE active
+--~}
+-- :name fetch_users :<> :*
+SELECT * FROM users
+--~{ limited
+LIMIT 1
+--~}
+"#;
+
+        generate_types(input);
+    }
+
+    #[test]
+    fn precomputed_sql_per_cfg() {
+        let input = r#"SELECT * FROM users
+--~where
+--~{ active
+active
+--~}
+--~{ named
+name = ?
+--~}
+--~cfg(unix)
+user_id > 0
+--~end
+--~end
+--~cfg(debug_assertions)
+LIMIT 1
+--~end"#;
+        let blocks = condblock::parse_sql_blocks(&input.chars().collect::<Vec<_>>());
+        let fields = [format_ident!("active"), format_ident!("named")];
+        let cfgs = ["unix".to_string(), "debug_assertions".to_string()];
+
+        // Predicates don't count as blocks, each combination of them gets its own table
+        let tables = precompute_sql(&blocks, &fields, &cfgs).unwrap();
+        assert_eq!(tables.len(), 4);
+        assert!(tables.iter().all(|table| table.len() == 4));
+        assert_eq!(tables[0][0], "\nSELECT * FROM users");
+        assert_eq!(tables[0][2], "\nSELECT * FROM users\nWHERE (name = ?)");
+        assert_eq!(tables[1][0], "\nSELECT * FROM users\nWHERE (user_id > 0)");
+        assert_eq!(tables[2][0], "\nSELECT * FROM users\nLIMIT 1");
+        assert_eq!(
+            tables[3][3],
+            "\nSELECT * FROM users\nWHERE (active)\nAND (name = ?)\nAND (user_id > 0)\nLIMIT 1"
+        );
+
+        let fields: Vec<_> = (0..MAX_PRECOMPUTED_BLOCKS)
+            .map(|i| format_ident!("block{}", i))
+            .collect();
+        let cfgs: Vec<_> = (0..MAX_PRECOMPUTED_CFGS)
+            .map(|i| format!("feature = \"f{}\"", i))
+            .collect();
+        assert!(precompute_sql(&blocks, &fields, &cfgs).is_some());
+        assert!(precompute_sql(&blocks, &fields, &[cfgs.clone(), cfgs].concat()).is_none());
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Cursor query users requires a database declaring cursors, like postgres"
+    )]
+    fn cursor_without_database_cursors() {
+        let input = r#"
+-- :name users :typed :cursor 100
+SELECT * FROM users
+"#;
+        generate(input);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Bulk query insert_users requires a database binding arrays, like postgres"
+    )]
+    fn bulk_without_arrays() {
+        let input = r#"
+-- :name insert_users :bulk
+-- :param users &[User]
+-- :fields users (user_id, email)
+INSERT INTO users(user_id, email)
+SELECT * FROM UNNEST($1::int[], $2::text[])
+"#;
+        generate(input);
+    }
+
+    #[test]
+    fn query_builders_buffer() {
+        let input = r#"
+-- :name fetch_users :<> :*
+SELECT * FROM users
+-- :name fetch_users_by_ids :<> :*
+SELECT * FROM users WHERE user_id IN (:v*:ids)
+"#;
+
+        let functions = generate(input);
+        let builder = |name: &str| {
+            let start = functions.find(&format!("fn {name} <")).unwrap();
+            functions[start..].split_once('{').unwrap().0.to_string()
+        };
+        assert!(!builder("fetch_users_query").contains("mut String"));
+        assert!(builder("fetch_users_by_ids_query").contains("sql : & 'q mut String"));
+    }
+
+    #[test]
+    fn query_builders_skip_affects() {
+        let input = r#"
+-- :name touch_users :affects 1
+UPDATE users SET name = name
+"#;
+
+        assert!(!generate(input).contains("fn touch_users_query"));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Query builder of query fetch_users collides with query fetch_users_query"
+    )]
+    fn query_builders_collision() {
+        let input = r#"
+-- :name fetch_users :<> :*
+SELECT * FROM users
+-- :name fetch_users_query :<> :*
+SELECT * FROM users
+"#;
+
+        generate(input);
+    }
+
+    #[test]
+    fn value_list_in_optional_block() {
+        let input = r#"
+-- :name fetch_tagged_users :<> :*
+SELECT * FROM users
+--~{ ?tag
+WHERE tag = :tag AND user_id IN (:v*:ids)
+--~}
+"#;
+
+        let functions = generate(input);
+        assert!(!functions.contains(":v*:ids"));
+        assert!(functions.contains("tag : Option < impl sqlx :: Encode"));
+        assert!(functions.contains("ids : & 'q [impl sqlx :: Encode"));
+        assert!(functions.contains("for (i , item) in ids . iter () . enumerate ()"));
+    }
+
+    #[test]
+    fn identifiers_in_optional_and_loop_blocks() {
+        let input = r#"
+-- :name fetch_sorted_users :<> :*
+-- :ident sort_column in (name, email)
+SELECT * FROM users
+--~{ ?name
+WHERE :i:name_column = :name
+--~}
+--~for tag in tags
+AND :i:tag_column LIKE :tag
+--~end
+--~{ ?limit
+ORDER BY :i:sort_column LIMIT :limit
+--~}
+"#;
+
+        let functions = generate(input);
+        assert!(!functions.contains(":i:"));
+        assert!(functions.contains("name_column : & str"));
+        assert!(functions.contains("tag_column : & str"));
+        assert!(functions.contains("sort_column : FetchSortedUsersSortColumn"));
+        assert!(functions.contains("quote_identifier (\"name_column\" , name_column , '\"')"));
+        assert!(functions.contains("sql . push_str (sort_column . as_quoted ())"));
+    }
+
+    #[test]
+    fn tuple_list_expansion() {
+        let input = r#"
+-- :name insert_users
+-- :param rows Vec<(i32, String)>
+INSERT INTO users(user_id, name) VALUES :t*:rows
+"#;
+
+        let functions = generate(input);
+        assert!(!functions.contains(":t*:rows"));
+        assert!(functions.contains("rows : & 'q [(i32 , String)]"));
+        assert!(functions.contains(
+            "for (i , item) in rows . iter () . enumerate () { sql . push_str (if i > 0 { \", (\" } else { \"(\" }) ; params . add (& item . 0)"
+        ));
+        assert!(functions.contains("sql . push_str (\", \") ; params . add (& item . 1)"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Tuple list :t*:rows can't be repeated by --~for tag in tags block")]
+    fn tuple_list_in_loop_block() {
+        let input = r#"
+-- :name insert_users
+-- :param rows Vec<(i32, String)>
+--~for tag in tags
+INSERT INTO users(user_id, name) VALUES :t*:rows
+--~end
+"#;
+
+        generate(input);
+    }
 }
@@ -1,4 +1,5 @@
 use chumsky::prelude::*;
+use std::collections::BTreeMap;
 
 use crate::condblock::{self, SqlBlock};
 
@@ -7,6 +8,8 @@ pub enum Kind {
     Typed,
     Untyped,
     Mapped,
+    TryMapped,
+    AsyncMapped,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -15,47 +18,168 @@ pub enum Method {
     FetchOne,
     FetchOptional,
     FetchMany,
+    /// Stream of vectors of up to given number of rows
+    FetchChunks(usize),
+    FetchScalar,
+    Exists,
     Execute,
+    ExecuteRaw,
+    RowsAffected,
+    Bulk,
+    Batch,
+    Script,
+    /// Rows folded by a key column
+    Map(String),
+    Group(String),
+    /// Rows nested by a key column into parents, along with prefix of children columns
+    Nest(String, String),
+    /// Stream of rows fetched from a server-side cursor, given number of rows at once
+    Cursor(usize),
+    /// Page of rows, by offset or by keyset if query declares order keys
+    Paged,
+}
+
+/// Type declaration of a parameter passed to generated function
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Param {
+    pub name: String,
+    pub ty: String,
+}
+
+/// Inclusive range of rows expected to be affected by an execute query
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Affects {
+    pub min: u64,
+    pub max: Option<u64>,
+}
+
+/// Fields of slice elements bound as arrays by a bulk query, in order of placeholders
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fields {
+    pub name: String,
+    pub fields: Vec<String>,
+}
+
+/// Column a paged query is ordered by, along with the direction
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OrderKey {
+    pub column: String,
+    pub descending: bool,
+}
+
+/// Identifier passed to generated function, optionally restricted to allowed ones
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Identifier {
+    pub name: String,
+    pub allowed: Vec<String>,
 }
 
 #[derive(Debug, PartialEq, Eq)]
 enum Element {
-    Signature(String, Kind, Method),
+    Signature(String, Kind, Method, Option<Affects>),
     Doc(String),
+    Param(Param),
+    Ident(Identifier),
+    Fields(Fields),
+    OrderKey(Vec<OrderKey>),
+    Cfg(String),
+    Snippet(String),
     Sql(Vec<SqlBlock>),
 }
 
+/// Either a query or a snippet, which is not turned into a function, but gets included
+/// by queries instead.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Definition {
+    Query(Box<Query>),
+    Snippet(String, Vec<SqlBlock>),
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Query {
     pub name: String,
     pub kind: Kind,
     pub method: Method,
+    pub affects: Option<Affects>,
     pub doc: Option<String>,
+    pub cfg: Option<String>,
+    pub params: Vec<Param>,
+    pub identifiers: Vec<Identifier>,
+    pub fields: Option<Fields>,
+    /// Columns a paged query is ordered by, to fetch pages by keyset rather than offset
+    pub order_key: Vec<OrderKey>,
     pub sql: Vec<SqlBlock>,
 }
 
+impl Definition {
+    fn from(elements: Vec<Element>) -> Self {
+        let snippet = elements.iter().find_map(|e| match e {
+            Element::Snippet(name) => Some(name.clone()),
+            _ => None,
+        });
+        match snippet {
+            Some(name) => {
+                let sql = elements.into_iter().find_map(|e| match e {
+                    Element::Sql(s) => Some(s),
+                    _ => None,
+                });
+                Definition::Snippet(name, sql.expect("SQL block is required for a snippet"))
+            }
+            None => Definition::Query(Box::new(Query::from(elements))),
+        }
+    }
+}
+
 impl Query {
     fn from(elements: Vec<Element>) -> Self {
         let mut name = String::default();
         let mut doc = None;
+        let mut cfg = None;
+        let mut params = Vec::new();
+        let mut identifiers = Vec::new();
+        let mut fields = None;
+        let mut order_key = Vec::new();
         let mut sql = None;
         let mut kind = Kind::Typed;
         let mut method = Method::FetchAll;
+        let mut affects = None;
 
         for e in elements {
             match e {
-                Element::Signature(n, t, m) => {
+                Element::Signature(n, t, m, a) => {
                     name = n;
                     kind = t;
                     method = m;
+                    affects = a;
                 }
                 Element::Doc(d) => doc = Some(d),
+                Element::Param(p) => params.push(p),
+                Element::Ident(i) => identifiers.push(i),
+                Element::Fields(f) => fields = Some(f),
+                Element::OrderKey(k) => order_key = k,
+                Element::Cfg(c) => cfg = Some(c),
+                Element::Snippet(_) => {}
                 Element::Sql(s) => sql = Some(s),
             }
         }
 
         let sql = sql.expect("SQL block is required for a query");
 
+        if fields.is_some() && method != Method::Bulk {
+            panic!(":fields mapping of query {} requires :bulk hint", name);
+        }
+        if affects.is_some()
+            && !matches!(
+                method,
+                Method::Execute | Method::ExecuteRaw | Method::RowsAffected
+            )
+        {
+            panic!(":affects of query {} requires execute query", name);
+        }
+        if !order_key.is_empty() && method != Method::Paged {
+            panic!(":order_key of query {} requires :paged hint", name);
+        }
+
         if name.is_empty() {
             panic!(
                 ":name attribute is missing or is not a valid identifier. Query: \"{:?}\"",
@@ -66,21 +190,56 @@ impl Query {
             name,
             kind,
             method,
+            affects,
             doc,
+            cfg,
+            params,
+            identifiers,
+            fields,
+            order_key,
             sql,
         }
     }
 }
 
-pub(crate) fn query_parser() -> impl Parser<char, Vec<Query>, Error = Simple<char>> {
+pub(crate) fn query_parser() -> impl Parser<char, Vec<Definition>, Error = Simple<char>> {
     let comment = just("--").padded();
+    let chunk_size = text::int(10).map(|n: String| match n.parse::<usize>() {
+        Ok(size) if size > 0 => size,
+        _ => panic!("Invalid size of chunks: {}", n),
+    });
     let arity = just(':')
         .ignore_then(choice((
             just('!').to(Method::Execute),
             just('1').to(Method::FetchOne),
             just('?').to(Method::FetchOptional),
             just('*').to(Method::FetchAll),
+            just('^').ignore_then(chunk_size).map(Method::FetchChunks),
             just('^').to(Method::FetchMany),
+            text::keyword("chunks")
+                .ignore_then(chunk_size.padded())
+                .map(Method::FetchChunks),
+            text::keyword("paged").to(Method::Paged),
+            text::keyword("cursor")
+                .ignore_then(chunk_size.padded())
+                .map(Method::Cursor),
+            text::keyword("scalar").to(Method::FetchScalar),
+            text::keyword("exists").to(Method::Exists),
+            text::keyword("n").to(Method::RowsAffected),
+            text::keyword("raw").to(Method::ExecuteRaw),
+            text::keyword("bulk").to(Method::Bulk),
+            text::keyword("batch").to(Method::Batch),
+            text::keyword("script").to(Method::Script),
+            text::keyword("map")
+                .ignore_then(text::ident().padded())
+                .map(Method::Map),
+            text::keyword("group")
+                .ignore_then(text::ident().padded())
+                .map(Method::Group),
+            text::keyword("nest")
+                .ignore_then(text::ident().padded())
+                .then(text::ident().padded())
+                .map(|(key, prefix)| Method::Nest(key, prefix)),
         )))
         .padded()
         .labelled("arity");
@@ -91,22 +250,57 @@ pub(crate) fn query_parser() -> impl Parser<char, Vec<Query>, Error = Simple<cha
             just("||").to(Kind::Mapped),
             text::keyword("typed").to(Kind::Typed),
             text::keyword("mapped").to(Kind::Mapped),
+            text::keyword("try_mapped").to(Kind::TryMapped),
+            text::keyword("async_mapped").to(Kind::AsyncMapped),
             text::keyword("untyped").to(Kind::Untyped),
         )))
         .padded()
         .labelled("type");
 
+    let count = text::int(10).map(|n: String| n.parse::<u64>().unwrap());
+    let affects = just(':')
+        .ignore_then(text::keyword("affects"))
+        .padded()
+        .ignore_then(
+            count.then(
+                just("..")
+                    .ignore_then(just('=').or_not().then(count.or_not()))
+                    .or_not(),
+            ),
+        )
+        .padded()
+        .map(|(min, range)| {
+            let max =
+                match range {
+                    None => Some(min),
+                    Some((Some(_), Some(max))) => Some(max),
+                    Some((None, Some(end))) => Some(end.checked_sub(1).unwrap_or_else(|| {
+                        panic!("Empty range of affected rows: {}..{}", min, end)
+                    })),
+                    Some((None, None)) => None,
+                    Some((Some(_), None)) => {
+                        panic!("Inclusive range of affected rows needs an end: {}..=", min)
+                    }
+                };
+            if max.is_some_and(|max| max < min) {
+                panic!("Empty range of affected rows: {}..={}", min, max.unwrap());
+            }
+            Affects { min, max }
+        })
+        .labelled("affects");
+
     let signature = comment
         .ignore_then(just(':'))
         .ignore_then(just("name").padded())
         .ignore_then(text::ident())
         .padded()
-        .then(kind.or_not().then(arity.or_not()))
-        .map(|(ident, (t, a))| {
+        .then(kind.or_not().then(arity.or_not()).then(affects.or_not()))
+        .map(|(ident, ((t, a), affects))| {
             Element::Signature(
                 ident,
                 t.unwrap_or(Kind::Untyped),
                 a.unwrap_or(Method::Execute),
+                affects,
             )
         })
         .labelled("name");
@@ -117,6 +311,7 @@ pub(crate) fn query_parser() -> impl Parser<char, Vec<Query>, Error = Simple<cha
         .ignore_then(take_until(just('\n')))
         .then(
             comment
+                .ignore_then(none_of(':').rewind())
                 .ignore_then(take_until(just('\n')))
                 .padded()
                 .repeated(),
@@ -129,8 +324,111 @@ pub(crate) fn query_parser() -> impl Parser<char, Vec<Query>, Error = Simple<cha
         .map(|(v, _)| Element::Doc(v.iter().collect::<String>()))
         .labelled("doc");
 
-    let signature_cloned = signature.clone();
-    let sql = take_until(signature_cloned.or(doc).rewind().ignored().or(end()))
+    let param = comment
+        .ignore_then(just(':'))
+        .ignore_then(text::keyword("param").padded())
+        .ignore_then(text::ident())
+        .then(take_until(just('\n')))
+        .map(|(name, (ty, _))| {
+            Element::Param(Param {
+                name,
+                ty: ty.iter().collect::<String>().trim().to_string(),
+            })
+        })
+        .labelled("param");
+
+    let ident = comment
+        .ignore_then(just(':'))
+        .ignore_then(text::keyword("ident").padded())
+        .ignore_then(text::ident())
+        .then(
+            text::keyword("in")
+                .padded()
+                .ignore_then(
+                    text::ident()
+                        .padded()
+                        .separated_by(just(','))
+                        .delimited_by(just('('), just(')')),
+                )
+                .or_not(),
+        )
+        .padded()
+        .map(|(name, allowed)| {
+            Element::Ident(Identifier {
+                name,
+                allowed: allowed.unwrap_or_default(),
+            })
+        })
+        .labelled("ident");
+
+    let fields = comment
+        .ignore_then(just(':'))
+        .ignore_then(text::keyword("fields").padded())
+        .ignore_then(text::ident())
+        .then(
+            text::ident()
+                .padded()
+                .separated_by(just(','))
+                .at_least(1)
+                .delimited_by(just('('), just(')'))
+                .padded(),
+        )
+        .padded()
+        .map(|(name, fields)| Element::Fields(Fields { name, fields }))
+        .labelled("fields");
+
+    // Direction needs to follow its column on the same line, not to be confused with SQL
+    let inline = one_of(" \t").repeated();
+    let direction = inline.clone().ignore_then(choice((
+        text::keyword("ASC").to(false),
+        text::keyword("asc").to(false),
+        text::keyword("DESC").to(true),
+        text::keyword("desc").to(true),
+    )));
+    let order_key = comment
+        .ignore_then(just(':'))
+        .ignore_then(text::keyword("order_key").padded())
+        .ignore_then(
+            inline
+                .clone()
+                .ignore_then(text::ident())
+                .then(direction.or_not())
+                .then_ignore(inline)
+                .map(|(column, descending)| OrderKey {
+                    column,
+                    descending: descending.unwrap_or(false),
+                })
+                .separated_by(just(','))
+                .at_least(1),
+        )
+        .padded()
+        .map(Element::OrderKey)
+        .labelled("order_key");
+
+    let cfg = comment
+        .ignore_then(just(':'))
+        .ignore_then(text::keyword("cfg"))
+        .ignore_then(take_until(just('\n')))
+        .map(|(predicate, _)| Element::Cfg(predicate.iter().collect::<String>().trim().to_string()))
+        .labelled("cfg");
+
+    let snippet = comment
+        .ignore_then(just(':'))
+        .ignore_then(text::keyword("snippet").padded())
+        .ignore_then(text::ident())
+        .padded()
+        .map(Element::Snippet)
+        .labelled("snippet");
+
+    let header = signature
+        .or(doc)
+        .or(param)
+        .or(ident)
+        .or(fields)
+        .or(order_key)
+        .or(cfg)
+        .or(snippet);
+    let sql = take_until(header.clone().rewind().ignored().or(end()))
         .padded()
         .map(|(v, _)| {
             let blocks = condblock::parse_sql_blocks(&v);
@@ -138,17 +436,37 @@ pub(crate) fn query_parser() -> impl Parser<char, Vec<Query>, Error = Simple<cha
         })
         .labelled("sql");
 
-    let query = signature
-        .or(doc)
+    let query = header
         .repeated()
         .at_least(1)
-        .at_most(2)
         .chain(sql)
-        .map(Query::from);
+        .map(Definition::from);
 
     query.repeated().then_ignore(end())
 }
 
-pub fn parse_queries(input: String) -> Result<Vec<Query>, Vec<Simple<char>>> {
+pub(crate) fn parse_queries(input: String) -> Result<Vec<Definition>, Vec<Simple<char>>> {
     query_parser().parse(input)
 }
+
+/// Merges definitions parsed from all the inputs into queries, expanding snippets they
+/// include. Snippets defined in one input might be included by queries of any other one.
+pub(crate) fn expand_snippets(definitions: Vec<Definition>) -> Vec<Query> {
+    let mut queries = Vec::new();
+    let mut snippets = BTreeMap::new();
+
+    for definition in definitions {
+        match definition {
+            Definition::Query(query) => queries.push(*query),
+            Definition::Snippet(name, sql) => {
+                if snippets.insert(name.clone(), sql).is_some() {
+                    panic!("Snippet {} is defined more than once", name);
+                }
+            }
+        }
+    }
+    for query in queries.iter_mut() {
+        query.sql = condblock::expand_includes(&query.sql, &snippets);
+    }
+    queries
+}
@@ -0,0 +1,857 @@
+//! Helpers used by the code generated with `#[derive(HugSqlx)]`. Not a public API.
+
+use crate::condblock::skip_quoted;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Maximal length of an identifier, the lowest one among supported databases
+const MAX_IDENTIFIER_LEN: usize = 63;
+
+/// Clause gluing parts of SQL, which might be included or not.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Clause {
+    Where,
+    Set,
+}
+
+impl Clause {
+    /// Appends `parts` glued with a separator to `sql`, prefixed with a clause keyword.
+    /// Connectives dangling at the beginning or end of a part are removed, while empty
+    /// parts are skipped. Nothing gets appended if all the parts are empty, in which case
+    /// `false` is returned. Parts of WHERE clause get parenthesized, so that `OR` inside
+    /// a part doesn't bind weaker than `AND`.
+    pub fn push(self, sql: &mut String, parts: &[String]) -> bool {
+        let (keyword, separator, connectives): (_, _, &[&str]) = match self {
+            Clause::Where => ("WHERE", "\nAND ", &["AND", "OR"]),
+            Clause::Set => ("SET", ",\n", &[","]),
+        };
+        let mut parts = parts
+            .iter()
+            .map(|part| strip_connectives(part, connectives))
+            .filter(|part| !part.is_empty())
+            .peekable();
+
+        if parts.peek().is_none() {
+            return false;
+        }
+        sql.push('\n');
+        sql.push_str(keyword);
+        sql.push(' ');
+        for (i, part) in parts.enumerate() {
+            if i > 0 {
+                sql.push_str(separator);
+            }
+            match self {
+                Clause::Where => {
+                    sql.push('(');
+                    sql.push_str(part);
+                    // Line comment ending a part would swallow the parenthesis
+                    if part.lines().last().is_some_and(|line| line.contains("--")) {
+                        sql.push('\n');
+                    }
+                    sql.push(')');
+                }
+                Clause::Set => sql.push_str(part),
+            }
+        }
+        true
+    }
+}
+
+/// Number of tuples of given `arity` bound to a single statement along with `bound` other
+/// parameters, so that no more than `limit` parameters get bound. There is at least one
+/// tuple per statement, leaving it to database to report too many parameters otherwise.
+pub fn chunk_len(limit: usize, bound: usize, arity: usize) -> usize {
+    (limit.saturating_sub(bound) / arity).max(1)
+}
+
+/// Strips semicolon terminating statement along with comments following it, so that the
+/// statement can be wrapped into a subquery. Semicolons and comments within quoted strings
+/// are left intact.
+pub fn strip_terminator(sql: &str) -> &str {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut end = 0;
+    let mut terminated = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match skip_quoted(&chars, i) {
+            Some(next) => {
+                if !matches!(c, '-' | '/') {
+                    end = next;
+                    terminated = false;
+                }
+                i = next;
+            }
+            None => {
+                if !c.is_whitespace() {
+                    end = i + 1;
+                    terminated = c == ';';
+                }
+                i += 1;
+            }
+        }
+    }
+    let end = if terminated { end - 1 } else { end };
+    let end = sql
+        .char_indices()
+        .nth(end)
+        .map_or(sql.len(), |(pos, _)| pos);
+    sql[..end].trim_end()
+}
+
+/// Wraps paged query into a statement counting all of its rows.
+pub fn count_sql(query: &str) -> String {
+    ["SELECT COUNT(*) FROM (\n", query, "\n) AS page"].concat()
+}
+
+/// Limits paged query to `limit` rows following the first `offset` ones.
+pub fn offset_sql(query: &str, limit: usize, offset: u64) -> String {
+    format!("{}\nLIMIT {} OFFSET {}", query, limit, offset)
+}
+
+/// Wraps paged query into a subquery ordered by `columns` in their directions and limited
+/// to `limit` rows. Given `keys` of the last row of previous page, rows get filtered to the
+/// ones following it, with placeholder of each key pushed by `bind`, called with the key
+/// index and value. Keys of the same direction get compared as a row, while the mixed ones
+/// get compared column by column, binding a key once per each of its occurrences.
+pub fn keyset_sql<E>(
+    query: &str,
+    columns: &[(&str, SortDirection)],
+    keys: Option<&[String]>,
+    limit: usize,
+    mut bind: impl FnMut(&mut String, usize, &str) -> Result<(), E>,
+) -> Result<String, E> {
+    let following = |direction: SortDirection| match direction {
+        SortDirection::Asc => " > ",
+        SortDirection::Desc => " < ",
+    };
+    let mut sql = ["SELECT * FROM (\n", query, "\n) AS page"].concat();
+    if let (Some(keys), Some((_, first))) = (keys, columns.first()) {
+        sql.push_str("\nWHERE ");
+        if columns.iter().all(|(_, direction)| direction == first) {
+            sql.push('(');
+            sql.push_str(
+                &columns
+                    .iter()
+                    .map(|(column, _)| *column)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            );
+            sql.push(')');
+            sql.push_str(following(*first));
+            sql.push('(');
+            for (i, key) in keys.iter().enumerate() {
+                if i > 0 {
+                    sql.push_str(", ");
+                }
+                bind(&mut sql, i, key)?;
+            }
+            sql.push(')');
+        } else {
+            // Row follows if it ties on all the preceding keys and follows on the next one
+            sql.push('(');
+            for (i, (column, direction)) in columns.iter().enumerate() {
+                if i > 0 {
+                    sql.push_str(" OR ");
+                }
+                sql.push('(');
+                for (j, (tied, _)) in columns[..i].iter().enumerate() {
+                    sql.push_str(tied);
+                    sql.push_str(" = ");
+                    bind(&mut sql, j, &keys[j])?;
+                    sql.push_str(" AND ");
+                }
+                sql.push_str(column);
+                sql.push_str(following(*direction));
+                bind(&mut sql, i, &keys[i])?;
+                sql.push(')');
+            }
+            sql.push(')');
+        }
+    }
+    sql.push_str("\nORDER BY ");
+    for (i, (column, direction)) in columns.iter().enumerate() {
+        if i > 0 {
+            sql.push_str(", ");
+        }
+        sql.push_str(column);
+        if *direction == SortDirection::Desc {
+            sql.push_str(" DESC");
+        }
+    }
+    sql.push_str(" LIMIT ");
+    sql.push_str(&limit.to_string());
+    Ok(sql)
+}
+
+/// Returns name of a server-side cursor of given query, unique within the process, so that
+/// the same query might have many cursors open on a single connection. Counter goes first,
+/// as database might truncate long names.
+pub fn cursor_name(query: &str) -> String {
+    static CURSORS: AtomicU64 = AtomicU64::new(0);
+    format!(
+        "hugsqlx_{}_{}",
+        CURSORS.fetch_add(1, Ordering::Relaxed),
+        query
+    )
+}
+
+/// Identifiers known only at runtime, like per-tenant schema names, substituted in place
+/// of `{{:name}}` slots of a query.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Identifiers(BTreeMap<String, String>);
+
+impl Identifiers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets identifier substituted in place of `{{:name}}` slot.
+    pub fn with(mut self, name: impl Into<String>, identifier: impl Into<String>) -> Self {
+        self.0.insert(name.into(), identifier.into());
+        self
+    }
+
+    /// Returns identifier of `name` slot wrapped in `quote` characters. Fails if identifier
+    /// is missing or does not match strict identifier grammar: ASCII letter or underscore
+    /// followed by ASCII letters, digits or underscores.
+    pub fn quoted(&self, name: &str, quote: char) -> Result<String, IdentifierError> {
+        let identifier = self
+            .0
+            .get(name)
+            .ok_or_else(|| IdentifierError::Missing(name.to_string()))?;
+        quote_identifier(name, identifier, quote)
+    }
+}
+
+/// Wraps identifier passed for `name` slot in `quote` characters. Fails if identifier does
+/// not match strict identifier grammar.
+pub fn quote_identifier(
+    name: &str,
+    identifier: &str,
+    quote: char,
+) -> Result<String, IdentifierError> {
+    if !is_valid_identifier(identifier) {
+        return Err(IdentifierError::Invalid(
+            name.to_string(),
+            identifier.to_string(),
+        ));
+    }
+    Ok(format!("{quote}{identifier}{quote}"))
+}
+
+fn is_valid_identifier(identifier: &str) -> bool {
+    let mut chars = identifier.chars();
+    identifier.len() <= MAX_IDENTIFIER_LEN
+        && matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Error of substituting identifier slots.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IdentifierError {
+    /// No identifier provided for a slot of given name
+    Missing(String),
+    /// Identifier provided for a slot of given name is not a valid one
+    Invalid(String, String),
+}
+
+impl fmt::Display for IdentifierError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IdentifierError::Missing(name) => {
+                write!(f, "No identifier provided for {} slot", name)
+            }
+            IdentifierError::Invalid(name, identifier) => write!(
+                f,
+                "Invalid identifier provided for {} slot: {:?}",
+                name, identifier
+            ),
+        }
+    }
+}
+
+impl std::error::Error for IdentifierError {}
+
+/// Direction of `ORDER BY` clause of a column chosen at runtime.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum SortDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+impl SortDirection {
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            SortDirection::Asc => "ASC",
+            SortDirection::Desc => "DESC",
+        }
+    }
+}
+
+/// Error of a batch query, which rolled back all the statements run so far.
+#[derive(Debug)]
+pub struct BatchError<E> {
+    /// Index of parameter set which failed, none if transaction itself failed
+    pub index: Option<usize>,
+    pub error: E,
+}
+
+impl<E> BatchError<E> {
+    /// Wraps an error of transaction itself.
+    pub fn transaction(error: E) -> Self {
+        BatchError { index: None, error }
+    }
+}
+
+/// Outcome of an execute query, the same for all the databases.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ExecOutcome {
+    pub rows_affected: u64,
+    /// Id of the last inserted row, reported by sqlite and mysql only (if any row was inserted)
+    pub last_insert_id: Option<i64>,
+}
+
+/// Error of an execute query with `:affects` hint. Changes made by the query get rolled
+/// back either way.
+#[derive(Debug)]
+pub enum RowsAffectedError<E> {
+    /// Query affected unexpected number of rows
+    Unexpected {
+        query: &'static str,
+        /// Expected range of affected rows, as declared with `:affects` hint
+        expected: &'static str,
+        rows_affected: u64,
+    },
+    /// Query itself (or its transaction) failed
+    Query(E),
+}
+
+impl<E: fmt::Display> fmt::Display for RowsAffectedError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RowsAffectedError::Unexpected {
+                query,
+                expected,
+                rows_affected,
+            } => write!(
+                f,
+                "Query {} affected {} rows, expected {}",
+                query, rows_affected, expected
+            ),
+            RowsAffectedError::Query(error) => error.fmt(f),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for RowsAffectedError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RowsAffectedError::Unexpected { .. } => None,
+            RowsAffectedError::Query(error) => Some(error),
+        }
+    }
+}
+
+/// Page of results returned by a paged query.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Cursor of the next page, if there is one
+    pub next_cursor: Option<PageCursor>,
+    pub has_more: bool,
+    /// Number of all the rows returned by unpaged query, if requested
+    pub total: Option<u64>,
+}
+
+/// Page requested from a paged query: its size, cursor returned along with previous page
+/// (none for the first page), and whether total number of rows should be counted too.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PageRequest {
+    pub size: usize,
+    pub cursor: Option<PageCursor>,
+    pub total: bool,
+}
+
+impl PageRequest {
+    /// Requests the first page of given size.
+    pub fn first(size: usize) -> Self {
+        PageRequest {
+            size,
+            cursor: None,
+            total: false,
+        }
+    }
+
+    /// Requests a page following the one which returned given cursor.
+    pub fn after(size: usize, cursor: PageCursor) -> Self {
+        PageRequest {
+            size,
+            cursor: Some(cursor),
+            total: false,
+        }
+    }
+
+    /// Requests total number of rows to be counted along with the page.
+    pub fn with_total(self) -> Self {
+        PageRequest {
+            total: true,
+            ..self
+        }
+    }
+}
+
+/// Opaque position of a page, either an offset or values of the last row's order keys.
+/// Might be stored or sent over the wire as a string and parsed back.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PageCursor(String);
+
+impl PageCursor {
+    #[doc(hidden)]
+    pub fn from_offset(offset: u64) -> Self {
+        PageCursor(format!("o{}", offset))
+    }
+
+    #[doc(hidden)]
+    pub fn from_keys(keys: &[String]) -> Self {
+        let keys = keys
+            .iter()
+            .map(|key| key.replace('\\', "\\\\").replace(',', "\\,"))
+            .collect::<Vec<_>>();
+        PageCursor(format!("k{}", keys.join(",")))
+    }
+
+    #[doc(hidden)]
+    pub fn offset(&self) -> Option<u64> {
+        self.0.strip_prefix('o')?.parse().ok()
+    }
+
+    #[doc(hidden)]
+    pub fn keys(&self) -> Option<Vec<String>> {
+        let mut chars = self.0.strip_prefix('k')?.chars();
+        let mut keys = vec![String::new()];
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => keys.last_mut()?.push(chars.next()?),
+                ',' => keys.push(String::new()),
+                c => keys.last_mut()?.push(c),
+            }
+        }
+        Some(keys)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Value of an order key of a paged query, stored in a page cursor as a string. Conversion
+/// needs to be lossless, as the value parsed back gets compared with the keys of rows to
+/// find the ones following the last row of previous page.
+///
+/// Implemented for integers, floats, strings and booleans, and for chrono date and time
+/// types with `chrono` feature.
+pub trait PageKey: Sized {
+    fn to_key(&self) -> String;
+    fn from_key(key: &str) -> Option<Self>;
+}
+
+macro_rules! page_key_via_display {
+    ($($ty:ty),*) => {
+        $(
+            impl PageKey for $ty {
+                fn to_key(&self) -> String {
+                    self.to_string()
+                }
+
+                fn from_key(key: &str) -> Option<Self> {
+                    key.parse().ok()
+                }
+            }
+        )*
+    };
+}
+
+page_key_via_display!(i8, i16, i32, i64, u8, u16, u32, u64, bool, String);
+
+// Floats keep their bits, not to depend on precision of the decimal representation
+impl PageKey for f32 {
+    fn to_key(&self) -> String {
+        format!("{:x}", self.to_bits())
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        u32::from_str_radix(key, 16).ok().map(f32::from_bits)
+    }
+}
+
+impl PageKey for f64 {
+    fn to_key(&self) -> String {
+        format!("{:x}", self.to_bits())
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        u64::from_str_radix(key, 16).ok().map(f64::from_bits)
+    }
+}
+
+// Display of chrono types is meant for humans, it doesn't always parse back
+#[cfg(feature = "chrono")]
+const DATE_TIME_KEY: &str = "%Y-%m-%dT%H:%M:%S%.f";
+
+#[cfg(feature = "chrono")]
+impl PageKey for chrono::NaiveDateTime {
+    fn to_key(&self) -> String {
+        self.format(DATE_TIME_KEY).to_string()
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        chrono::NaiveDateTime::parse_from_str(key, DATE_TIME_KEY).ok()
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl PageKey for chrono::NaiveDate {
+    fn to_key(&self) -> String {
+        self.format("%Y-%m-%d").to_string()
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        chrono::NaiveDate::parse_from_str(key, "%Y-%m-%d").ok()
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl PageKey for chrono::DateTime<chrono::Utc> {
+    fn to_key(&self) -> String {
+        self.to_rfc3339_opts(chrono::SecondsFormat::Nanos, true)
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        chrono::DateTime::parse_from_rfc3339(key)
+            .ok()
+            .map(|time| time.with_timezone(&chrono::Utc))
+    }
+}
+
+impl fmt::Display for PageCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<String> for PageCursor {
+    fn from(cursor: String) -> Self {
+        PageCursor(cursor)
+    }
+}
+
+/// Result of a single statement of a script query.
+#[derive(Debug)]
+pub struct StatementResult<R> {
+    pub sql: &'static str,
+    pub result: R,
+}
+
+impl<E: fmt::Display> fmt::Display for BatchError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.index {
+            Some(index) => write!(f, "Batch failed at parameter set {}: {}", index, self.error),
+            None => write!(f, "Batch transaction failed: {}", self.error),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for BatchError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+fn strip_connectives<'a>(part: &'a str, connectives: &[&str]) -> &'a str {
+    let mut part = part.trim();
+    while let Some(stripped) = connectives
+        .iter()
+        .find_map(|c| strip_prefix(part, c).or_else(|| strip_suffix(part, c)))
+    {
+        part = stripped.trim();
+    }
+    part
+}
+
+/// Word-like connectives need to be separated from the rest, not to cut "ORDER" into "DER".
+fn is_separated(connective: &str, next: Option<char>) -> bool {
+    !connective.starts_with(char::is_alphabetic)
+        || next.is_none_or(|c| c.is_whitespace() || c == '(' || c == ')')
+}
+
+fn strip_prefix<'a>(part: &'a str, connective: &str) -> Option<&'a str> {
+    let (head, rest) = part.split_at_checked(connective.len())?;
+    (head.eq_ignore_ascii_case(connective) && is_separated(connective, rest.chars().next()))
+        .then_some(rest)
+}
+
+fn strip_suffix<'a>(part: &'a str, connective: &str) -> Option<&'a str> {
+    let (rest, tail) = part.split_at_checked(part.len().checked_sub(connective.len())?)?;
+    (tail.eq_ignore_ascii_case(connective) && is_separated(connective, rest.chars().next_back()))
+        .then_some(rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_where_clause() {
+        let mut sql = String::from("SELECT * FROM users");
+        let parts = [
+            "\nAND name = $1".to_string(),
+            String::new(),
+            "\nemail = $2 OR".to_string(),
+            "\nORDER_NO > 0".to_string(),
+        ];
+        Clause::Where.push(&mut sql, &parts);
+
+        assert_eq!(
+            sql,
+            "SELECT * FROM users\nWHERE (name = $1)\nAND (email = $2)\nAND (ORDER_NO > 0)"
+        );
+    }
+
+    #[test]
+    fn test_where_clause_alternatives() {
+        let mut sql = String::from("SELECT * FROM users");
+        let parts = [
+            "\nstatus = ?\nOR\nstatus = ?".to_string(),
+            "\nAND id < 3 -- recent ones".to_string(),
+        ];
+        Clause::Where.push(&mut sql, &parts);
+
+        assert_eq!(
+            sql,
+            "SELECT * FROM users\nWHERE (status = ?\nOR\nstatus = ?)\nAND (id < 3 -- recent ones\n)"
+        );
+    }
+
+    #[test]
+    fn test_set_clause_trailing_commas() {
+        let mut sql = String::from("UPDATE users");
+        let parts = ["name = ?,".to_string(), ", email = ?,".to_string()];
+        assert!(Clause::Set.push(&mut sql, &parts));
+
+        assert_eq!(sql, "UPDATE users\nSET name = ?,\nemail = ?");
+    }
+
+    #[test]
+    fn test_empty_clause() {
+        let mut sql = String::from("SELECT * FROM users");
+        assert!(!Clause::Where.push(&mut sql, &[String::new()]));
+
+        assert_eq!(sql, "SELECT * FROM users");
+
+        let mut sql = String::from("UPDATE users");
+        assert!(!Clause::Set.push(&mut sql, &[",".to_string()]));
+
+        assert_eq!(sql, "UPDATE users");
+    }
+
+    #[test]
+    fn test_chunk_len() {
+        assert_eq!(chunk_len(999, 0, 4), 249);
+        assert_eq!(chunk_len(999, 3, 4), 249);
+        assert_eq!(chunk_len(999, 4, 4), 248);
+        assert_eq!(chunk_len(999, 998, 1), 1);
+        assert_eq!(chunk_len(999, 999, 4), 1);
+    }
+
+    #[test]
+    fn test_strip_terminator() {
+        assert_eq!(strip_terminator("SELECT 1"), "SELECT 1");
+        assert_eq!(strip_terminator("SELECT 1;\n"), "SELECT 1");
+        assert_eq!(strip_terminator("SELECT 1 -- one\n"), "SELECT 1");
+        assert_eq!(strip_terminator("SELECT 1; -- note"), "SELECT 1");
+        assert_eq!(
+            strip_terminator("SELECT 1 /* one */;\n/* note */"),
+            "SELECT 1 /* one */"
+        );
+        assert_eq!(strip_terminator("SELECT ';' -- ;"), "SELECT ';'");
+        assert_eq!(strip_terminator("SELECT 'ą';"), "SELECT 'ą'");
+    }
+
+    #[test]
+    fn test_offset_paging() {
+        let query = "SELECT * FROM users ORDER BY user_id";
+        assert_eq!(
+            count_sql(query),
+            "SELECT COUNT(*) FROM (\nSELECT * FROM users ORDER BY user_id\n) AS page"
+        );
+        assert_eq!(
+            offset_sql(query, 11, 20),
+            "SELECT * FROM users ORDER BY user_id\nLIMIT 11 OFFSET 20"
+        );
+    }
+
+    #[test]
+    fn test_keyset_paging() {
+        let query = "SELECT * FROM users WHERE name = ?";
+        let mut bound = Vec::new();
+        let mut bind = |sql: &mut String, i: usize, key: &str| -> Result<(), ()> {
+            bound.push((i, key.to_string()));
+            sql.push('?');
+            Ok(())
+        };
+
+        let ascending = [
+            ("created_at", SortDirection::Asc),
+            ("user_id", SortDirection::Asc),
+        ];
+        assert_eq!(
+            keyset_sql(query, &ascending, None, 3, &mut bind),
+            Ok("SELECT * FROM (\nSELECT * FROM users WHERE name = ?\n) AS page\nORDER BY created_at, user_id LIMIT 3".to_string())
+        );
+        let keys = ["2024-01-01".to_string(), "7".to_string()];
+        assert_eq!(
+            keyset_sql(query, &ascending, Some(&keys), 3, &mut bind),
+            Ok("SELECT * FROM (\nSELECT * FROM users WHERE name = ?\n) AS page\nWHERE (created_at, user_id) > (?, ?)\nORDER BY created_at, user_id LIMIT 3".to_string())
+        );
+        assert_eq!(bound, [(0, "2024-01-01".to_string()), (1, "7".to_string())]);
+
+        let failing = |_: &mut String, _: usize, _: &str| Err("invalid key");
+        assert_eq!(
+            keyset_sql(query, &ascending[1..], Some(&keys[1..]), 3, failing),
+            Err("invalid key")
+        );
+    }
+
+    #[test]
+    fn test_descending_keyset_paging() {
+        // Placeholders get numbered, like postgres ones
+        let render = |columns: &[(&str, SortDirection)]| {
+            let keys = ["2024-01-01".to_string(), "7".to_string()];
+            let mut bound = Vec::new();
+            let sql = keyset_sql(
+                "SELECT * FROM users",
+                columns,
+                Some(&keys),
+                3,
+                |sql, _, key| {
+                    bound.push(key.to_string());
+                    sql.push_str(&format!("${}", bound.len()));
+                    Ok::<_, ()>(())
+                },
+            );
+            (sql.unwrap(), bound)
+        };
+
+        let (sql, bound) = render(&[
+            ("created_at", SortDirection::Desc),
+            ("user_id", SortDirection::Desc),
+        ]);
+        assert_eq!(
+            sql,
+            "SELECT * FROM (\nSELECT * FROM users\n) AS page\nWHERE (created_at, user_id) < ($1, $2)\nORDER BY created_at DESC, user_id DESC LIMIT 3"
+        );
+        assert_eq!(bound, ["2024-01-01", "7"]);
+
+        // Mixed directions can't be compared as a row, first key gets bound twice
+        let (sql, bound) = render(&[
+            ("created_at", SortDirection::Desc),
+            ("user_id", SortDirection::Asc),
+        ]);
+        assert_eq!(
+            sql,
+            "SELECT * FROM (\nSELECT * FROM users\n) AS page\nWHERE ((created_at < $1) OR (created_at = $2 AND user_id > $3))\nORDER BY created_at DESC, user_id LIMIT 3"
+        );
+        assert_eq!(bound, ["2024-01-01", "2024-01-01", "7"]);
+    }
+
+    #[test]
+    fn test_cursor_name() {
+        let first = cursor_name("users");
+        let second = cursor_name("users");
+        assert!(first.starts_with("hugsqlx_") && first.ends_with("_users"));
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn test_identifiers() {
+        let idents = Identifiers::new().with("schema", "tenant_1");
+
+        assert_eq!(idents.quoted("schema", '"'), Ok("\"tenant_1\"".to_string()));
+        assert_eq!(
+            idents.quoted("prefix", '`'),
+            Err(IdentifierError::Missing("prefix".to_string()))
+        );
+        assert_eq!(
+            Identifiers::new()
+                .with("schema", "app\"; DROP TABLE users; --")
+                .quoted("schema", '"'),
+            Err(IdentifierError::Invalid(
+                "schema".to_string(),
+                "app\"; DROP TABLE users; --".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_page_cursor() {
+        let keys = vec![
+            "2024-01-01 10:00:00".to_string(),
+            "a,b\\c".to_string(),
+            String::new(),
+        ];
+        let cursor = PageCursor::from_keys(&keys);
+        assert_eq!(cursor.keys(), Some(keys));
+        assert_eq!(cursor.offset(), None);
+
+        let cursor = PageCursor::from(PageCursor::from_offset(40).to_string());
+        assert_eq!(cursor.offset(), Some(40));
+        assert_eq!(cursor.keys(), None);
+        assert_eq!(PageCursor::from("k1\\".to_string()).keys(), None);
+    }
+
+    #[test]
+    fn test_page_keys() {
+        let keys = [
+            0.1f64.to_key(),
+            (-7i64).to_key(),
+            "a,b".to_string().to_key(),
+        ];
+        let keys = PageCursor::from(PageCursor::from_keys(&keys).to_string())
+            .keys()
+            .unwrap();
+        assert_eq!(f64::from_key(&keys[0]), Some(0.1));
+        assert_eq!(i64::from_key(&keys[1]), Some(-7));
+        assert_eq!(String::from_key(&keys[2]), Some("a,b".to_string()));
+        assert_eq!(f32::from_key("not a float"), None);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_created_at_page_key() {
+        // Display of NaiveDateTime separates date and time with a space, which FromStr rejects
+        let created_at = chrono::NaiveDate::from_ymd_opt(2024, 1, 1)
+            .unwrap()
+            .and_hms_nano_opt(10, 0, 0, 123_456_789)
+            .unwrap();
+        assert!(created_at
+            .to_string()
+            .parse::<chrono::NaiveDateTime>()
+            .is_err());
+
+        let cursor = PageCursor::from_keys(&[created_at.to_key(), 7i32.to_key()]);
+        let keys = PageCursor::from(cursor.to_string()).keys().unwrap();
+        assert_eq!(chrono::NaiveDateTime::from_key(&keys[0]), Some(created_at));
+        assert_eq!(i32::from_key(&keys[1]), Some(7));
+
+        let utc = created_at.and_utc();
+        assert_eq!(chrono::DateTime::from_key(&utc.to_key()), Some(utc));
+        let date = created_at.date();
+        assert_eq!(chrono::NaiveDate::from_key(&date.to_key()), Some(date));
+    }
+}
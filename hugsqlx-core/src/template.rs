@@ -0,0 +1,186 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    env, fs,
+    path::Path,
+};
+
+use crate::condblock::{is_identifier, skip_quoted};
+
+/// Name of the file with template variables, looked up in crate's and workspace directory
+pub(crate) const VARS_FILE: &str = "hugsqlx.toml";
+
+/// Prefix of environment variables overriding template variables
+const ENV_PREFIX: &str = "HUGSQLX_VAR_";
+
+/// Compile-time variables substituted in place of `{{name}}` markers of query files.
+#[derive(Default)]
+pub(crate) struct Vars {
+    values: BTreeMap<String, String>,
+    /// Names of variables substituted so far
+    used: RefCell<BTreeSet<String>>,
+}
+
+impl Vars {
+    /// Creates variables from derive attribute, falling back to those defined in `[vars]`
+    /// table of a vars file (if provided).
+    pub(crate) fn new(attribute_vars: BTreeMap<String, String>, vars_file: Option<&Path>) -> Self {
+        let mut values = vars_file.map(read_vars_file).unwrap_or_default();
+        values.extend(attribute_vars);
+        Vars {
+            values,
+            used: RefCell::default(),
+        }
+    }
+
+    /// Returns value of a variable. Environment variable (`HUGSQLX_VAR_` followed by
+    /// upper-cased variable name) takes precedence over all the other definitions.
+    fn get(&self, name: &str) -> Option<String> {
+        self.used.borrow_mut().insert(name.to_string());
+        env::var(env_var(name))
+            .ok()
+            .or_else(|| self.values.get(name).cloned())
+    }
+
+    /// Returns names of environment variables which may override variables substituted
+    /// so far, so that the caller can have them tracked for changes.
+    pub(crate) fn env_vars(&self) -> Vec<String> {
+        self.used
+            .borrow()
+            .iter()
+            .map(|name| env_var(name))
+            .collect()
+    }
+
+    /// Replaces all the `{{name}}` markers with values of corresponding variables. String
+    /// literals and comments are left intact, unlike quoted identifiers, which may need
+    /// a variable part. Panics if any of variables is not defined.
+    pub(crate) fn substitute(&self, input: &str) -> String {
+        let chars: Vec<char> = input.chars().collect();
+        let mut result = String::with_capacity(input.len());
+        let mut i = 0;
+
+        while i < chars.len() {
+            if let Some(end) = skip_quoted(&chars, i).filter(|_| !matches!(chars[i], '"' | '`')) {
+                result.extend(&chars[i..end]);
+                i = end;
+                continue;
+            }
+            if chars[i] != '{' || chars.get(i + 1) != Some(&'{') {
+                result.push(chars[i]);
+                i += 1;
+                continue;
+            }
+            let marker = &chars[i + 2..];
+            match marker
+                .windows(2)
+                .position(|w| w == ['}', '}'])
+                .map(|end| (marker[..end].iter().collect::<String>(), end + 4))
+                .filter(|(name, _)| is_identifier(name.trim()))
+            {
+                Some((name, len)) => {
+                    let name = name.trim();
+                    let value = self.get(name).unwrap_or_else(|| {
+                        panic!("Template variable {{{{{}}}}} is not defined", name)
+                    });
+                    result.push_str(&value);
+                    i += len;
+                }
+                // Not a variable marker, like a nested array literal
+                None => {
+                    result.push_str("{{");
+                    i += 2;
+                }
+            }
+        }
+        result
+    }
+}
+
+fn env_var(name: &str) -> String {
+    format!("{}{}", ENV_PREFIX, name.to_uppercase())
+}
+
+fn read_vars_file(path: &Path) -> BTreeMap<String, String> {
+    let content = fs::read_to_string(path)
+        .unwrap_or_else(|err| panic!("Could not read {}: {}", path.display(), err));
+    let value = content
+        .parse::<toml::Value>()
+        .unwrap_or_else(|err| panic!("Invalid {}: {}", path.display(), err));
+
+    match value.get("vars") {
+        Some(toml::Value::Table(vars)) => vars
+            .iter()
+            .map(|(name, value)| match value {
+                toml::Value::String(s) => (name.clone(), s.clone()),
+                _ => panic!(
+                    "Template variable {} in {} must be a string",
+                    name,
+                    path.display()
+                ),
+            })
+            .collect(),
+        Some(_) => panic!("[vars] in {} must be a table", path.display()),
+        None => BTreeMap::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_substitution() {
+        let vars = Vars::new(
+            BTreeMap::from([("schema".to_string(), "app".to_string())]),
+            None,
+        );
+
+        assert_eq!(
+            vars.substitute("SELECT * FROM {{schema}}.users JOIN {{ schema }}.tags"),
+            "SELECT * FROM app.users JOIN app.tags"
+        );
+        assert_eq!(
+            vars.substitute("SELECT '{{1,2},{3,4}}'::int[][]"),
+            "SELECT '{{1,2},{3,4}}'::int[][]"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Template variable {{prefix}} is not defined")]
+    fn test_undefined_variable() {
+        Vars::default().substitute("SELECT * FROM {{prefix}}users");
+    }
+
+    #[test]
+    fn test_quoted_markers() {
+        let vars = Vars::new(
+            BTreeMap::from([("schema".to_string(), "app".to_string())]),
+            None,
+        );
+
+        assert_eq!(
+            vars.substitute(
+                "SELECT * FROM {{schema}}.users -- see {{docs}}\nWHERE note = '{{draft}}'"
+            ),
+            "SELECT * FROM app.users -- see {{docs}}\nWHERE note = '{{draft}}'"
+        );
+        assert_eq!(vars.env_vars(), vec!["HUGSQLX_VAR_SCHEMA".to_string()]);
+    }
+
+    #[test]
+    fn test_quoted_identifiers() {
+        let vars = Vars::new(
+            BTreeMap::from([
+                ("schema".to_string(), "app".to_string()),
+                ("prefix".to_string(), "tenant_".to_string()),
+            ]),
+            None,
+        );
+
+        assert_eq!(
+            vars.substitute(r#"SELECT * FROM "{{schema}}".users JOIN `{{prefix}}tags`"#),
+            r#"SELECT * FROM "app".users JOIN `tenant_tags`"#
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use hugsqlx_core::{Context, ContextType};
 use proc_macro::TokenStream;
 
-#[proc_macro_derive(HugSqlx, attributes(queries))]
+#[proc_macro_derive(HugSqlx, attributes(queries, hugsqlx))]
 pub fn hugsqlx(input_stream: TokenStream) -> TokenStream {
     let ast = syn::parse(input_stream).unwrap();
     let ctx = Context::new(if cfg!(feature = "postgres") {
@@ -1,2 +1,44 @@
 pub use hugsqlx_core::params;
+pub use hugsqlx_core::runtime::{
+    BatchError, ExecOutcome, IdentifierError, Identifiers, Page, PageCursor, PageKey, PageRequest,
+    RowsAffectedError, SortDirection, StatementResult,
+};
 pub use hugsqlx_derive::HugSqlx;
+
+#[doc(hidden)]
+pub mod runtime;
+
+/// Decides which conditional blocks get included into the query.
+///
+/// Implemented by functions taking a block enum variant, and by generated `*Flags` structs.
+pub trait BlockResolver<B> {
+    fn resolve(&self, block: B) -> bool;
+}
+
+impl<B, F: Fn(B) -> bool> BlockResolver<B> for F {
+    fn resolve(&self, block: B) -> bool {
+        self(block)
+    }
+}
+
+/// Parent of rows nested by a `:nest` query, collecting its children.
+///
+/// Usually implemented by a struct deriving `sqlx::FromRow`, with children skipped by
+/// `#[sqlx(skip)]`. Children implement [`NestChild`].
+pub trait Nest {
+    type Child;
+    fn children(&mut self) -> &mut Vec<Self::Child>;
+}
+
+/// Child of rows nested by a `:nest` query, decoded from the columns of a row `R` whose
+/// names start with the prefix declared by the query.
+///
+/// sqlx can't decode a row with its columns renamed, so the child gets each column by its
+/// unprefixed name with `prefix` prepended. This lets the same struct decode elsewhere
+/// from unprefixed columns, with `sqlx::FromRow`.
+pub trait NestChild<R>: Sized {
+    fn from_prefixed_row(
+        row: &R,
+        prefix: &str,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>>;
+}
@@ -0,0 +1,64 @@
+//! Helpers used by the code generated with `#[derive(HugSqlx)]`. Not a public API.
+
+use futures_core::{stream::BoxStream, Stream};
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+pub use async_stream::stream;
+pub use hugsqlx_core::runtime::{
+    chunk_len, count_sql, cursor_name, keyset_sql, offset_sql, quote_identifier, strip_terminator,
+    Clause,
+};
+
+/// Batches results of given stream into vectors of up to `size` items. Items preceding
+/// an error are yielded before the error, which ends the stream.
+pub fn chunks<'e, T, E>(
+    mut items: BoxStream<'e, Result<T, E>>,
+    size: usize,
+) -> BoxStream<'e, Result<Vec<T>, E>>
+where
+    T: Send + 'e,
+    E: Send + 'e,
+{
+    Box::pin(stream! {
+        let mut chunk = Vec::with_capacity(size);
+        while let Some(item) = next(&mut items).await {
+            match item {
+                Ok(item) => {
+                    chunk.push(item);
+                    if chunk.len() == size {
+                        yield Ok(std::mem::replace(&mut chunk, Vec::with_capacity(size)));
+                    }
+                }
+                Err(e) => {
+                    if !chunk.is_empty() {
+                        yield Ok(chunk);
+                    }
+                    yield Err(e);
+                    return;
+                }
+            }
+        }
+        if !chunk.is_empty() {
+            yield Ok(chunk);
+        }
+    })
+}
+
+/// Polls next item of given stream.
+pub fn next<S: Stream + Unpin>(stream: &mut S) -> Next<'_, S> {
+    Next(stream)
+}
+
+pub struct Next<'s, S>(&'s mut S);
+
+impl<S: Stream + Unpin> Future for Next<'_, S> {
+    type Output = Option<S::Item>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut *self.0).poll_next(cx)
+    }
+}